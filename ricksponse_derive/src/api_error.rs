@@ -0,0 +1,217 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::meta::ParseNestedMeta;
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, DeriveInput, Fields, Ident, LitInt, LitStr, Member};
+
+#[derive(Default, Clone)]
+struct Attributes {
+    status: Option<LitInt>,
+    code: Option<LitStr>,
+    message: Option<LitStr>,
+}
+
+impl Attributes {
+    /// The attributes of a variant, falling back to those of the enum.
+    fn or(self, defaults: &Attributes) -> Attributes {
+        Attributes {
+            status: self.status.or_else(|| defaults.status.clone()),
+            code: self.code.or_else(|| defaults.code.clone()),
+            message: self.message.or_else(|| defaults.message.clone()),
+        }
+    }
+}
+
+/// A struct, or a variant of an enum, with everything needed to render its arm of the `match`.
+struct Case {
+    path: TokenStream,
+    attributes: Attributes,
+    bindings: Vec<(Member, Ident)>,
+    details: Option<Ident>,
+}
+
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let defaults = parse_attributes(&input.attrs)?;
+    let cases = match &input.data {
+        Data::Struct(data) => vec![case(quote!(Self), defaults, &data.fields)?],
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| {
+                let ident = &variant.ident;
+                let attributes = parse_attributes(&variant.attrs)?.or(&defaults);
+                case(quote!(Self::#ident), attributes, &variant.fields)
+            })
+            .collect::<syn::Result<Vec<_>>>()?,
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                "`ApiError` cannot be derived for unions",
+            ))
+        }
+    };
+
+    let status = arms(&cases, |case| match &case.attributes.status {
+        Some(status) => quote!(#status),
+        None => quote!(500),
+    });
+    let code = arms(&cases, |case| match &case.attributes.code {
+        Some(code) => quote!(::std::option::Option::Some(#code)),
+        None => quote!(::std::option::Option::None),
+    });
+    let message = arms(&cases, |case| match &case.attributes.message {
+        Some(message) => {
+            let message = LitStr::new(&positional_to_named(&message.value()), message.span());
+            quote!(::std::option::Option::Some(::std::format!(#message)))
+        }
+        None => quote!(::std::option::Option::None),
+    });
+    let details = arms(&cases, |case| match &case.details {
+        Some(binding) => quote!(::ricksponse::__private::to_details(#binding)),
+        None => quote!(::std::option::Option::None),
+    });
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::ricksponse::ApiError for #name #ty_generics #where_clause {
+            fn status(&self) -> u16 {
+                match *self { #(#status)* }
+            }
+
+            fn code(&self) -> ::std::option::Option<&str> {
+                match *self { #(#code)* }
+            }
+
+            fn message(&self) -> ::std::option::Option<::std::string::String> {
+                match *self { #(#message)* }
+            }
+
+            fn details(&self) -> ::std::option::Option<::ricksponse::__private::Value> {
+                match *self { #(#details)* }
+            }
+        }
+    })
+}
+
+fn arms(cases: &[Case], body: impl Fn(&Case) -> TokenStream) -> Vec<TokenStream> {
+    cases
+        .iter()
+        .map(|case| {
+            let path = &case.path;
+            let (members, bindings): (Vec<_>, Vec<_>) = case.bindings.iter().cloned().unzip();
+            let body = body(case);
+            quote! {
+                #[allow(unused_variables)]
+                #path { #(#members: ref #bindings,)* .. } => #body,
+            }
+        })
+        .collect()
+}
+
+fn case(path: TokenStream, attributes: Attributes, fields: &Fields) -> syn::Result<Case> {
+    let mut bindings = Vec::new();
+    let mut details = None;
+    for (index, field) in fields.iter().enumerate() {
+        let (member, binding) = match &field.ident {
+            Some(ident) => (Member::Named(ident.clone()), ident.clone()),
+            None => (Member::Unnamed(index.into()), format_ident!("_{}", index)),
+        };
+        if is_details(&field.attrs)? {
+            if details.is_some() {
+                return Err(syn::Error::new(
+                    field.span(),
+                    "only one field can hold the `details`",
+                ));
+            }
+            details = Some(binding.clone());
+        }
+        bindings.push((member, binding));
+    }
+    Ok(Case {
+        path,
+        attributes,
+        bindings,
+        details,
+    })
+}
+
+fn parse_attributes(attrs: &[Attribute]) -> syn::Result<Attributes> {
+    let mut attributes = Attributes::default();
+    for attr in attrs.iter().filter(|a| a.path().is_ident("api_error")) {
+        attr.parse_nested_meta(|meta| parse_key(&mut attributes, meta))?;
+    }
+    Ok(attributes)
+}
+
+fn parse_key(attributes: &mut Attributes, meta: ParseNestedMeta) -> syn::Result<()> {
+    if meta.path.is_ident("status") {
+        let status: LitInt = meta.value()?.parse()?;
+        if !(100..1000).contains(&status.base10_parse::<u16>()?) {
+            return Err(syn::Error::new(
+                status.span(),
+                "status must be a HTTP status code between 100 and 999",
+            ));
+        }
+        set_once(&mut attributes.status, status, &meta)
+    } else if meta.path.is_ident("code") {
+        set_once(&mut attributes.code, meta.value()?.parse()?, &meta)
+    } else if meta.path.is_ident("message") {
+        set_once(&mut attributes.message, meta.value()?.parse()?, &meta)
+    } else {
+        Err(meta.error("unknown api_error attribute, expected `status`, `code` or `message`"))
+    }
+}
+
+fn is_details(attrs: &[Attribute]) -> syn::Result<bool> {
+    let mut details = false;
+    for attr in attrs.iter().filter(|a| a.path().is_ident("api_error")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("details") {
+                details = true;
+                Ok(())
+            } else {
+                Err(meta.error("unknown api_error field attribute, expected `details`"))
+            }
+        })?;
+    }
+    Ok(details)
+}
+
+fn set_once<T>(slot: &mut Option<T>, value: T, meta: &ParseNestedMeta) -> syn::Result<()> {
+    if slot.is_some() {
+        return Err(meta.error("duplicate api_error attribute"));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+/// Tuple fields are bound as `_0`, `_1`, ... so `{0}` in a message is rewritten to `{_0}` to be
+/// captured like named fields.
+fn positional_to_named(message: &str) -> String {
+    let mut named = String::with_capacity(message.len());
+    let mut chars = message.chars().peekable();
+    while let Some(c) = chars.next() {
+        named.push(c);
+        if c == '{' {
+            if chars.peek() == Some(&'{') {
+                named.push(chars.next().unwrap_or_default());
+            } else if chars.peek().is_some_and(char::is_ascii_digit) {
+                named.push('_');
+            }
+        }
+    }
+    named
+}
+
+#[cfg(test)]
+mod test {
+    use super::positional_to_named;
+
+    #[test]
+    fn test_positional_to_named() {
+        assert_eq!(positional_to_named("order {0}"), "order {_0}");
+        assert_eq!(positional_to_named("{1:>4} of {id}"), "{_1:>4} of {id}");
+        assert_eq!(positional_to_named("{{0}} {0}"), "{{0}} {_0}");
+    }
+}
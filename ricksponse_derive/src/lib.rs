@@ -0,0 +1,53 @@
+extern crate proc_macro;
+
+mod api_error;
+mod payload_control;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+/// Derives `ricksponse::PayloadControl` from a `#[payload(...)]` attribute.
+///
+/// ```ignore
+/// #[derive(Deserialize, PayloadControl)]
+/// #[payload(max_size = "10MiB", buffer = "64KiB", formats(json, cbor))]
+/// struct Upload {
+///     name: String,
+/// }
+/// ```
+///
+/// Sizes are either plain integers or strings with a unit, decimal (`kB`, `MB`, `GB`) or
+/// binary (`KiB`, `MiB`, `GiB`). Every key is optional, a missing key keeps the value of the
+/// `RicksponseConfig`.
+#[proc_macro_derive(PayloadControl, attributes(payload))]
+pub fn derive_payload_control(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    payload_control::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `ricksponse::ApiError` from `#[api_error(...)]` attributes.
+///
+/// ```ignore
+/// #[derive(ApiError, Debug)]
+/// #[api_error(status = 500, code = "order.internal")]
+/// enum OrderError {
+///     #[api_error(status = 404, code = "order.not_found", message = "No order with id {id}.")]
+///     NotFound { id: u32 },
+///     #[api_error(status = 409, message = "Order {0} is out of stock.")]
+///     OutOfStock(u32, #[api_error(details)] Stock),
+/// }
+/// ```
+///
+/// `status`, `code` and `message` can be set on a struct, an enum or a variant, a variant falls
+/// back to the attributes of its enum. Messages are formatted with the fields in scope, tuple
+/// fields by their index. The field marked with `#[api_error(details)]` is serialized as the
+/// details of the error.
+#[proc_macro_derive(ApiError, attributes(api_error))]
+pub fn derive_api_error(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    api_error::expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,209 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, quote_spanned};
+use syn::meta::ParseNestedMeta;
+use syn::{DeriveInput, Ident, Lit};
+
+/// Format names accepted by `formats(...)`, and the `ContentType` variant they map onto.
+const FORMATS: &[(&str, &str)] = &[
+    ("bson", "Bson"),
+    ("cbor", "Cbor"),
+    ("flexbuffers", "FlexBuffers"),
+    ("json", "Json"),
+    ("json5", "Json5"),
+    ("lexpr", "Lexpr"),
+    ("messagepack", "MessagePack"),
+    ("msgpack", "MessagePack"),
+    ("pickle", "Pickle"),
+    ("postcard", "Postcard"),
+    ("ron", "Ron"),
+    ("toml", "Toml"),
+    ("url", "Url"),
+    ("yaml", "Yaml"),
+];
+
+#[derive(Default)]
+struct Attributes {
+    max_size: Option<(u64, Span)>,
+    buffer: Option<(u64, Span)>,
+    formats: Option<Vec<Ident>>,
+}
+
+pub(crate) fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let mut attributes = Attributes::default();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("payload")) {
+        attr.parse_nested_meta(|meta| parse_key(&mut attributes, meta))?;
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let max_size = size_const(quote!(MAX_PAYLOAD_SIZE), attributes.max_size);
+    let buffer = size_const(quote!(BUFFER_CAPACITY), attributes.buffer);
+    let formats = attributes.formats.map(|formats| {
+        let variants = formats.iter().map(|format| {
+            let variant = Ident::new(format_variant(format).unwrap_or_default(), format.span());
+            quote_spanned!(format.span()=> ::ricksponse::ContentType::#variant)
+        });
+        quote! {
+            const FORMATS: ::std::option::Option<&'static [::ricksponse::ContentType]> =
+                ::std::option::Option::Some(&[#(#variants),*]);
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::ricksponse::PayloadControl for #name #ty_generics #where_clause {
+            #max_size
+            #buffer
+            #formats
+        }
+    })
+}
+
+fn parse_key(attributes: &mut Attributes, meta: ParseNestedMeta) -> syn::Result<()> {
+    if meta.path.is_ident("max_size") {
+        set_once(&mut attributes.max_size, parse_size_value(&meta)?, &meta)
+    } else if meta.path.is_ident("buffer") {
+        set_once(&mut attributes.buffer, parse_size_value(&meta)?, &meta)
+    } else if meta.path.is_ident("formats") {
+        let mut formats = Vec::new();
+        meta.parse_nested_meta(|format| {
+            let ident = format.path.require_ident()?;
+            if format_variant(ident).is_none() {
+                let known = FORMATS
+                    .iter()
+                    .map(|(f, _)| *f)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                return Err(format.error(format!(
+                    "unknown format `{}`, expected one of: {}",
+                    ident, known
+                )));
+            }
+            formats.push(ident.clone());
+            Ok(())
+        })?;
+        if formats.is_empty() {
+            return Err(meta.error("`formats` needs at least one format"));
+        }
+        set_once(&mut attributes.formats, formats, &meta)
+    } else {
+        Err(meta.error("unknown payload attribute, expected `max_size`, `buffer` or `formats`"))
+    }
+}
+
+fn set_once<T>(slot: &mut Option<T>, value: T, meta: &ParseNestedMeta) -> syn::Result<()> {
+    if slot.is_some() {
+        return Err(meta.error("duplicate payload attribute"));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+fn parse_size_value(meta: &ParseNestedMeta) -> syn::Result<(u64, Span)> {
+    let lit: Lit = meta.value()?.parse()?;
+    let size = match &lit {
+        Lit::Str(s) => parse_size(&s.value()),
+        Lit::Int(i) => i.base10_parse::<u64>().map_err(|e| e.to_string()),
+        _ => Err("expected a size such as \"10MiB\" or an integer".to_string()),
+    };
+    size.map(|size| (size, lit.span()))
+        .map_err(|message| syn::Error::new(lit.span(), message))
+}
+
+fn size_const(name: TokenStream, size: Option<(u64, Span)>) -> Option<TokenStream> {
+    size.map(|(size, span)| {
+        // `usize` may be narrower than `u64`, the assertion turns a truncation into a compile
+        // error on the target.
+        quote_spanned! {span=>
+            const #name: ::std::option::Option<usize> = {
+                assert!(#size as u128 <= usize::MAX as u128, "size does not fit in usize");
+                ::std::option::Option::Some(#size as usize)
+            };
+        }
+    })
+}
+
+fn format_variant(ident: &Ident) -> Option<&'static str> {
+    let name = ident.to_string().to_ascii_lowercase();
+    FORMATS
+        .iter()
+        .find(|(format, _)| *format == name)
+        .map(|(_, variant)| *variant)
+}
+
+/// Parses human-readable sizes such as `512`, `64KiB`, `1.5 MB` into bytes.
+pub(crate) fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '_'))
+        .unwrap_or(s.len());
+    let (number, unit) = (s[..split].replace('_', ""), s[split..].trim());
+    let multiplier: u64 = match unit {
+        "" | "B" => 1,
+        "kB" | "KB" | "K" => 1_000,
+        "MB" | "M" => 1_000_000,
+        "GB" | "G" => 1_000_000_000,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        _ => {
+            return Err(format!(
+                "unknown size unit `{}`, expected one of: B, kB, MB, GB, KiB, MiB, GiB",
+                unit
+            ))
+        }
+    };
+    let invalid = || format!("invalid size `{}`, expected a size such as \"10MiB\"", s);
+    let (whole, fraction) = number.split_once('.').unwrap_or((&number, ""));
+    if whole.is_empty() || fraction.contains('.') {
+        return Err(invalid());
+    }
+    let whole = whole.parse::<u64>().map_err(|_| invalid())?;
+    let fraction = if fraction.is_empty() {
+        0
+    } else {
+        let digits = fraction.parse::<u64>().map_err(|_| invalid())?;
+        let scale = 10u64
+            .checked_pow(fraction.len() as u32)
+            .ok_or_else(invalid)?;
+        let bytes = digits as u128 * multiplier as u128;
+        if bytes % scale as u128 != 0 {
+            return Err(format!("size `{}` is not a whole number of bytes", s));
+        }
+        (bytes / scale as u128) as u64
+    };
+    whole
+        .checked_mul(multiplier)
+        .and_then(|bytes| bytes.checked_add(fraction))
+        .ok_or_else(|| format!("size `{}` is too large", s))
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_size;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("512B"), Ok(512));
+        assert_eq!(parse_size("64KiB"), Ok(65_536));
+        assert_eq!(parse_size("10MiB"), Ok(10_485_760));
+        assert_eq!(parse_size("10 MB"), Ok(10_000_000));
+        assert_eq!(parse_size("1.5MiB"), Ok(1_572_864));
+        assert_eq!(parse_size("1_000kB"), Ok(1_000_000));
+        assert_eq!(parse_size("2GiB"), Ok(2_147_483_648));
+    }
+
+    #[test]
+    fn test_parse_size_failure() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("MiB").is_err());
+        assert!(parse_size("10XB")
+            .unwrap_err()
+            .contains("unknown size unit"));
+        assert!(parse_size("1.2.3MiB").is_err());
+        assert!(parse_size("0.3B").unwrap_err().contains("whole number"));
+        assert!(parse_size("99999999999GiB")
+            .unwrap_err()
+            .contains("too large"));
+    }
+}
@@ -0,0 +1,260 @@
+use crate::entity::payload_error::accept_header;
+use crate::entity::problem_details::ProblemDetails;
+use actix_web::error::InternalError;
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use serde_json::Value;
+use std::any::{Any, TypeId};
+use std::fmt::{Debug, Display};
+
+/// An error that knows how it is presented to API clients.
+///
+/// Errors stored in `Ricksponse::Error` are rendered as problem details from the status, code,
+/// message and details declared here. Only what the error returns from these methods reaches the
+/// client, the `Debug` representation is kept for logging.
+///
+/// Errors are `Send + Sync`, so a `Ricksponse` holding one can be moved across threads and await
+/// points. The concrete error can be recovered with `is`, `downcast_ref` and `downcast`.
+///
+/// Every method has a default, a bare `impl ApiError for MyError {}` answers with an anonymous
+/// `500 Internal Server Error`. With the `derive` feature the implementation can be derived:
+///
+#[cfg_attr(feature = "derive", doc = "```rust")]
+#[cfg_attr(not(feature = "derive"), doc = "```rust,ignore")]
+/// use ricksponse::ApiError;
+/// use serde_derive::Serialize;
+///
+/// #[derive(Serialize, Debug)]
+/// struct Stock {
+///     available: u32,
+/// }
+///
+/// #[derive(ApiError, Debug)]
+/// #[api_error(status = 500, code = "order.internal")]
+/// enum OrderError {
+///     #[api_error(status = 404, code = "order.not_found", message = "No order with id {id}.")]
+///     NotFound { id: u32 },
+///     #[api_error(status = 409, code = "order.out_of_stock", message = "Order {0} is out of stock.")]
+///     OutOfStock(u32, #[api_error(details)] Stock),
+///     Database(String),
+/// }
+///
+/// let error = OrderError::NotFound { id: 7 };
+/// assert_eq!(error.status(), 404);
+/// assert_eq!(error.code(), Some("order.not_found"));
+/// assert_eq!(error.message(), Some("No order with id 7.".to_string()));
+///
+/// let error = OrderError::OutOfStock(7, Stock { available: 0 });
+/// assert_eq!(error.details().unwrap()["available"], 0);
+///
+/// let error = OrderError::Database("connection refused".to_string());
+/// assert_eq!(error.status(), 500);
+/// assert_eq!(error.message(), None);
+/// ```
+pub trait ApiError: Debug + Any + Send + Sync {
+    /// The HTTP status code to respond with.
+    fn status(&self) -> u16 {
+        500
+    }
+
+    /// A stable, machine-readable identifier of the error, eg. `order.not_found`.
+    fn code(&self) -> Option<&str> {
+        None
+    }
+
+    /// A message that is safe to show to clients.
+    fn message(&self) -> Option<String> {
+        None
+    }
+
+    /// Structured details for clients, eg. the fields that failed validation.
+    fn details(&self) -> Option<Value> {
+        None
+    }
+
+    /// The problem details the error is rendered as. The code and details are added as the `code`
+    /// and `details` extension members.
+    fn problem(&self) -> ProblemDetails {
+        let mut problem = ProblemDetails::new(self.status());
+        problem.detail = self.message();
+        if let Some(code) = self.code() {
+            problem = problem.extension("code", code);
+        }
+        if let Some(details) = self.details() {
+            problem = problem.extension("details", details);
+        }
+        problem
+    }
+}
+
+impl dyn ApiError {
+    /// Returns `true` if the error is of type `E`.
+    pub fn is<E: ApiError>(&self) -> bool {
+        self.type_id() == TypeId::of::<E>()
+    }
+
+    /// Returns a reference to the error if it is of type `E`.
+    pub fn downcast_ref<E: ApiError>(&self) -> Option<&E> {
+        if self.is::<E>() {
+            // SAFETY: the type id was just checked to be the one of `E`.
+            unsafe { Some(&*(self as *const dyn ApiError as *const E)) }
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the error if it is of type `E`.
+    pub fn downcast_mut<E: ApiError>(&mut self) -> Option<&mut E> {
+        if self.is::<E>() {
+            // SAFETY: the type id was just checked to be the one of `E`.
+            unsafe { Some(&mut *(self as *mut dyn ApiError as *mut E)) }
+        } else {
+            None
+        }
+    }
+
+    /// Returns the boxed error as `E`, or gives the box back if it is of another type.
+    pub fn downcast<E: ApiError>(self: Box<Self>) -> Result<Box<E>, Box<dyn ApiError>> {
+        if self.is::<E>() {
+            // SAFETY: the type id was just checked to be the one of `E`.
+            unsafe { Ok(Box::from_raw(Box::into_raw(self) as *mut E)) }
+        } else {
+            Err(self)
+        }
+    }
+}
+
+/// Renders an error the way `Ricksponse::Error` is rendered, as problem details in the format
+/// negotiated from the `Accept` header of the request.
+///
+/// A payload rejected for its content type is answered with the media types it is accepted as,
+/// see `PayloadError::accepted`.
+pub fn error_response(error: &dyn ApiError, req: &HttpRequest) -> HttpResponse {
+    let mut res = error.problem().respond_to(req);
+    if let Some((name, value)) = accept_header(error, req) {
+        res.headers_mut().insert(name, value);
+    }
+    res
+}
+
+/// Turns an error into an `actix_web::Error` whose response is rendered by `error_response`.
+///
+/// `ResponseError` has no access to the request, this is the way to reject a request with a body
+/// the client is able to parse, eg. from a `RicksponseConfig::error_handler`:
+///
+/// ```rust
+/// use ricksponse::{negotiated_error, RicksponseConfig};
+///
+/// let config = RicksponseConfig::default().error_handler(negotiated_error);
+/// ```
+pub fn negotiated_error<E: ApiError + Display>(error: E, req: &HttpRequest) -> actix_web::Error {
+    let response = error_response(&error, req);
+    InternalError::from_response(error, response).into()
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod test {
+    use crate::{ApiError, ProblemDetails, Ricksponse};
+    use actix_web::test::TestRequest;
+    use actix_web::{body, Responder};
+    use http::StatusCode;
+
+    #[derive(Serialize, Debug)]
+    struct Field {
+        name: &'static str,
+        reason: &'static str,
+    }
+
+    #[derive(ApiError, Debug)]
+    #[api_error(status = 422, code = "order.invalid", message = "Order is invalid.")]
+    struct InvalidOrder {
+        #[api_error(details)]
+        fields: Vec<Field>,
+    }
+
+    #[derive(ApiError, Debug)]
+    #[api_error(code = "order.internal")]
+    enum OrderError {
+        #[api_error(
+            status = 404,
+            code = "order.not_found",
+            message = "No order with id {id}."
+        )]
+        NotFound {
+            id: u32,
+        },
+        #[api_error(status = 409, message = "Order {0} has {1:?} left.")]
+        OutOfStock(u32, u32),
+        Database(#[allow(dead_code)] String),
+    }
+
+    #[test]
+    fn test_derived_enum() {
+        let error = OrderError::NotFound { id: 7 };
+        assert_eq!(error.status(), 404);
+        assert_eq!(error.code(), Some("order.not_found"));
+        assert_eq!(error.message().unwrap(), "No order with id 7.");
+
+        let error = OrderError::OutOfStock(7, 0);
+        assert_eq!(error.status(), 409);
+        assert_eq!(error.code(), Some("order.internal"));
+        assert_eq!(error.message().unwrap(), "Order 7 has 0 left.");
+
+        let error = OrderError::Database("connection refused".to_string());
+        assert_eq!(error.status(), 500);
+        assert_eq!(error.message(), None);
+        assert_eq!(error.details(), None);
+    }
+
+    #[test]
+    fn test_derived_struct_problem() {
+        let error = InvalidOrder {
+            fields: vec![Field {
+                name: "quantity",
+                reason: "must be positive",
+            }],
+        };
+
+        assert_eq!(
+            error.problem(),
+            ProblemDetails::new(422)
+                .detail("Order is invalid.")
+                .extension("code", "order.invalid")
+                .extension(
+                    "details",
+                    serde_json::json!([{ "name": "quantity", "reason": "must be positive" }])
+                )
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_ricksponse_maps_api_error() {
+        let req = TestRequest::default().uri("/orders/7").to_http_request();
+
+        let result: Result<String, _> = Err(OrderError::NotFound { id: 7 });
+        let res = Ricksponse::<String>::from(result).respond_to(&req);
+
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let body = body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<ProblemDetails>(&body).unwrap(),
+            ProblemDetails::new(404)
+                .detail("No order with id 7.")
+                .instance("/orders/7")
+                .extension("code", "order.not_found")
+        );
+    }
+
+    #[cfg(feature = "hateoas")]
+    #[test]
+    fn test_hateoas_maps_api_error() {
+        use crate::hateoas::prelude::Hateoas as HateoasResponse;
+        use crate::hateoas::Hateoas;
+
+        let result: Result<HateoasResponse<String>, _> = Err(OrderError::NotFound { id: 7 });
+        let hateoas = Hateoas::<String>::from(result);
+        let status = hateoas.status().as_ref().unwrap();
+
+        assert_eq!(*status.http_status_code(), Some(404));
+        assert_eq!(status.message().as_deref(), Some("No order with id 7."));
+    }
+}
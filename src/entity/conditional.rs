@@ -0,0 +1,230 @@
+use actix_web::http::header::{
+    EntityTag, Header, HttpDate, IfMatch, IfModifiedSince, IfNoneMatch, IfUnmodifiedSince, ETAG,
+    IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_UNMODIFIED_SINCE, LAST_MODIFIED,
+};
+use actix_web::{HttpRequest, HttpResponse};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::Method;
+use std::str::FromStr;
+
+/// The outcome of evaluating the preconditions of a request, RFC 7232 section 6.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precondition {
+    /// No precondition failed, the request is to be served normally.
+    Passed,
+    /// The client has the current representation, answer `304 Not Modified`.
+    NotModified,
+    /// A precondition failed, answer `412 Precondition Failed`.
+    Failed,
+}
+
+/// How the entity tag of a response is obtained.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ETagSource {
+    /// A strong tag computed from the encoded body.
+    Strong,
+    /// A weak tag computed from the encoded body.
+    Weak,
+    /// A tag supplied by the application, eg. from a version column.
+    Version(EntityTag),
+}
+
+impl ETagSource {
+    pub(crate) fn entity_tag(self, body: &[u8]) -> EntityTag {
+        match self {
+            ETagSource::Strong => EntityTag::new_strong(digest(body)),
+            ETagSource::Weak => EntityTag::new_weak(digest(body)),
+            ETagSource::Version(tag) => tag,
+        }
+    }
+}
+
+/// Evaluates the `If-Match`, `If-Unmodified-Since`, `If-None-Match` and `If-Modified-Since`
+/// headers of a request against the current entity tag and modification date of the resource.
+///
+/// Responders evaluate the preconditions of `GET` and `HEAD` requests automatically. Handlers of
+/// state changing requests, such as `PUT`, evaluate them before making the change:
+///
+/// ```rust
+/// use actix_web::http::header::EntityTag;
+/// use actix_web::HttpRequest;
+/// use ricksponse::{evaluate_preconditions, Precondition, Ricksponse};
+///
+/// async fn update(req: HttpRequest, order: Ricksponse<String>) -> Ricksponse<String> {
+///     let current = EntityTag::new_strong("7".to_string());
+///     match evaluate_preconditions(&req, Some(&current), None) {
+///         Precondition::Passed => Ricksponse::OK("updated".to_string()),
+///         _ => Ricksponse::PRECONDITION_FAILED("changed meanwhile".to_string()),
+///     }
+/// }
+/// ```
+pub fn evaluate_preconditions(
+    req: &HttpRequest,
+    etag: Option<&EntityTag>,
+    last_modified: Option<HttpDate>,
+) -> Precondition {
+    let headers = req.headers();
+    if headers.contains_key(IF_MATCH) {
+        let matched = match IfMatch::parse(req) {
+            Ok(IfMatch::Any) => true,
+            Ok(IfMatch::Items(tags)) => {
+                etag.is_some_and(|etag| tags.iter().any(|tag| tag.strong_eq(etag)))
+            }
+            Err(_) => false,
+        };
+        if !matched {
+            return Precondition::Failed;
+        }
+    } else if headers.contains_key(IF_UNMODIFIED_SINCE) {
+        if let (Ok(IfUnmodifiedSince(since)), Some(last_modified)) =
+            (IfUnmodifiedSince::parse(req), last_modified)
+        {
+            if last_modified > since {
+                return Precondition::Failed;
+            }
+        }
+    }
+
+    let safe = req.method() == Method::GET || req.method() == Method::HEAD;
+    if headers.contains_key(IF_NONE_MATCH) {
+        let matched = match IfNoneMatch::parse(req) {
+            Ok(IfNoneMatch::Any) => true,
+            Ok(IfNoneMatch::Items(tags)) => {
+                etag.is_some_and(|etag| tags.iter().any(|tag| tag.weak_eq(etag)))
+            }
+            Err(_) => false,
+        };
+        if matched {
+            return if safe {
+                Precondition::NotModified
+            } else {
+                Precondition::Failed
+            };
+        }
+    } else if safe && headers.contains_key(IF_MODIFIED_SINCE) {
+        if let (Ok(IfModifiedSince(since)), Some(last_modified)) =
+            (IfModifiedSince::parse(req), last_modified)
+        {
+            if last_modified <= since {
+                return Precondition::NotModified;
+            }
+        }
+    }
+    Precondition::Passed
+}
+
+/// Evaluates the preconditions of a `GET` or `HEAD` request against the `ETag` and
+/// `Last-Modified` headers of a successful response.
+///
+/// The response of any other method is produced after the change was made, its preconditions are
+/// left to the handler, see `evaluate_preconditions`. A response without validators has nothing
+/// to evaluate the preconditions against and is always passed.
+pub(crate) fn evaluate_response(req: &HttpRequest, res: &HttpResponse) -> Precondition {
+    let safe = req.method() == Method::GET || req.method() == Method::HEAD;
+    if !safe || !res.status().is_success() {
+        return Precondition::Passed;
+    }
+    let header = |name| res.headers().get(name).and_then(|v| v.to_str().ok());
+    let etag = header(ETAG).and_then(|v| EntityTag::from_str(v).ok());
+    let last_modified = header(LAST_MODIFIED).and_then(|v| HttpDate::from_str(v).ok());
+    if etag.is_none() && last_modified.is_none() {
+        return Precondition::Passed;
+    }
+    evaluate_preconditions(req, etag.as_ref(), last_modified)
+}
+
+/// Turns a response into a `304 Not Modified`, keeping its headers but not its body.
+pub(crate) fn not_modified(res: HttpResponse) -> HttpResponse {
+    let mut not_modified = HttpResponse::NotModified().finish();
+    for (name, value) in res.headers() {
+        if name != CONTENT_TYPE && name != CONTENT_LENGTH {
+            not_modified
+                .headers_mut()
+                .append(name.clone(), value.clone());
+        }
+    }
+    not_modified
+}
+
+/// A 64 bit FNV-1a digest of the body, prefixed with its length.
+///
+/// The digest is stable across builds and platforms, unlike the hashers of `std`, so every
+/// instance of a service hands out the same tag for the same body.
+fn digest(body: &[u8]) -> String {
+    let hash = body.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{:x}-{:016x}", body.len(), hash)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{digest, evaluate_preconditions, Precondition};
+    use actix_web::http::header::{self, EntityTag, HttpDate};
+    use actix_web::test::TestRequest;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_digest() {
+        assert_eq!(digest(b""), "0-cbf29ce484222325");
+        assert_eq!(digest(b"a"), "1-af63dc4c8601ec8c");
+        assert_ne!(digest(b"ab"), digest(b"ba"));
+    }
+
+    #[test]
+    fn test_evaluate_preconditions() {
+        use header::{IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_UNMODIFIED_SINCE};
+        use Precondition::{Failed, NotModified, Passed};
+
+        let strong = EntityTag::new_strong("v2".to_string());
+        let weak = EntityTag::new_weak("v2".to_string());
+        let modified = HttpDate::from(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000));
+        let earlier = "Mon, 12 Jan 1970 13:46:39 GMT";
+        let later = "Mon, 12 Jan 1970 13:46:41 GMT";
+
+        for (method, name, value, etag, expected) in [
+            ("GET", IF_NONE_MATCH, r#""v2""#, &strong, NotModified),
+            ("GET", IF_NONE_MATCH, r#"W/"v2""#, &strong, NotModified),
+            ("GET", IF_NONE_MATCH, r#""v1""#, &strong, Passed),
+            ("GET", IF_NONE_MATCH, "*", &strong, NotModified),
+            ("PUT", IF_NONE_MATCH, "*", &strong, Failed),
+            ("PUT", IF_MATCH, r#""v2""#, &strong, Passed),
+            ("PUT", IF_MATCH, r#""v1", "v2""#, &strong, Passed),
+            ("PUT", IF_MATCH, r#""v1""#, &strong, Failed),
+            ("PUT", IF_MATCH, r#""v2""#, &weak, Failed),
+            ("GET", IF_MODIFIED_SINCE, later, &strong, NotModified),
+            ("GET", IF_MODIFIED_SINCE, earlier, &strong, Passed),
+            ("PUT", IF_MODIFIED_SINCE, later, &strong, Passed),
+            ("PUT", IF_UNMODIFIED_SINCE, earlier, &strong, Failed),
+            ("PUT", IF_UNMODIFIED_SINCE, later, &strong, Passed),
+        ] {
+            let req = TestRequest::default()
+                .method(method.parse().unwrap())
+                .insert_header((name.clone(), value))
+                .to_http_request();
+            assert_eq!(
+                evaluate_preconditions(&req, Some(etag), Some(modified)),
+                expected,
+                "{} {}: {}",
+                method,
+                name,
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_if_none_match_wins_over_if_modified_since() {
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, r#""v1""#))
+            .insert_header((header::IF_MODIFIED_SINCE, "Mon, 12 Jan 1970 13:46:41 GMT"))
+            .to_http_request();
+        let etag = EntityTag::new_strong("v2".to_string());
+        let modified = HttpDate::from(SystemTime::UNIX_EPOCH);
+
+        assert_eq!(
+            evaluate_preconditions(&req, Some(&etag), Some(modified)),
+            Precondition::Passed
+        );
+    }
+}
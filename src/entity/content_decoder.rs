@@ -0,0 +1,383 @@
+// Without a compression feature there is no decompressor, every encoded body is rejected.
+#![cfg_attr(
+    not(any(
+        feature = "compress-brotli",
+        feature = "compress-gzip",
+        feature = "compress-zstd"
+    )),
+    allow(dead_code, unreachable_code, unused_mut, unused_variables)
+)]
+
+use crate::entity::payload_error::PayloadError;
+use crate::entity::ricksponse_config::RicksponseConfig;
+use actix_http::error;
+use actix_http::header::{ContentEncoding, HeaderMap, CONTENT_ENCODING};
+use bytes::BytesMut;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// Bodies decompressing to less are not held to the compression ratio limit, a small body of
+/// repetitive data easily compresses beyond any sensible ratio.
+const RATIO_THRESHOLD: usize = 65_536;
+
+/// Collects the decompressed body and refuses to grow beyond the limits, so a compression bomb
+/// is stopped before it is inflated.
+#[derive(Default)]
+struct Output {
+    buf: BytesMut,
+    /// Compressed bytes fed to the decompressor so far.
+    read: usize,
+    limit: usize,
+    ratio: usize,
+    exceeded: Option<PayloadError>,
+}
+
+impl Write for Output {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let len = self.buf.len() + data.len();
+        if len > self.limit {
+            self.exceeded = Some(PayloadError::DecompressedOverflow { limit: self.limit });
+        } else if len > RATIO_THRESHOLD && len > self.read.saturating_mul(self.ratio) {
+            self.exceeded = Some(PayloadError::CompressionRatio { limit: self.ratio });
+        }
+        if self.exceeded.is_some() {
+            return Err(io::Error::other("decompression limit exceeded"));
+        }
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum Decompressor {
+    #[cfg(feature = "compress-gzip")]
+    Gzip(Box<flate2::write::GzDecoder<Output>>),
+    #[cfg(feature = "compress-gzip")]
+    Deflate(Box<flate2::write::ZlibDecoder<Output>>),
+    #[cfg(feature = "compress-brotli")]
+    Brotli(Box<brotli::DecompressorWriter<Output>>),
+    /// The writer under `zstd::stream::write::Decoder`, which alone reports an incomplete frame.
+    #[cfg(feature = "compress-zstd")]
+    Zstd(Box<zstd::stream::zio::Writer<Output, zstd::stream::raw::Decoder<'static>>>),
+}
+
+/// Decompresses a request body sent with a `Content-Encoding`.
+///
+/// The decompressed body is limited by `RicksponseConfig::decompressed_limit` and, to catch a
+/// bomb early, by `RicksponseConfig::compression_ratio_limit` against the compressed bytes read.
+pub(crate) struct ContentDecoder(Decompressor);
+
+impl ContentDecoder {
+    /// The decoder for the content encoding of a request, `None` when the body is not encoded.
+    pub(crate) fn from_headers(
+        headers: &HeaderMap,
+        config: &RicksponseConfig,
+    ) -> Result<Option<Self>, PayloadError> {
+        let encoding = match headers.get(CONTENT_ENCODING) {
+            Some(encoding) => encoding
+                .to_str()
+                .ok()
+                .and_then(|e| ContentEncoding::from_str(e.trim()).ok())
+                .ok_or_else(|| {
+                    PayloadError::ContentEncoding(
+                        String::from_utf8_lossy(encoding.as_bytes()).into_owned(),
+                    )
+                })?,
+            None => return Ok(None),
+        };
+        let output = Output {
+            buf: BytesMut::with_capacity(config.buffer_capacity),
+            read: 0,
+            limit: config.decompressed_limit,
+            ratio: config.compression_ratio,
+            exceeded: None,
+        };
+        let decompressor = match encoding {
+            ContentEncoding::Identity => return Ok(None),
+            #[cfg(feature = "compress-gzip")]
+            ContentEncoding::Gzip => {
+                Decompressor::Gzip(Box::new(flate2::write::GzDecoder::new(output)))
+            }
+            #[cfg(feature = "compress-gzip")]
+            ContentEncoding::Deflate => {
+                Decompressor::Deflate(Box::new(flate2::write::ZlibDecoder::new(output)))
+            }
+            #[cfg(feature = "compress-brotli")]
+            ContentEncoding::Brotli => {
+                Decompressor::Brotli(Box::new(brotli::DecompressorWriter::new(output, 8192)))
+            }
+            #[cfg(feature = "compress-zstd")]
+            ContentEncoding::Zstd => Decompressor::Zstd(Box::new(zstd::stream::zio::Writer::new(
+                output,
+                zstd::stream::raw::Decoder::new().map_err(Self::error)?,
+            ))),
+            encoding => return Err(PayloadError::ContentEncoding(encoding.as_str().to_string())),
+        };
+        Ok(Some(ContentDecoder(decompressor)))
+    }
+
+    /// Decompress the next chunk of the body.
+    pub(crate) fn feed(&mut self, chunk: &[u8]) -> Result<(), PayloadError> {
+        self.output().read += chunk.len();
+        let res: io::Result<()> = match self.0 {
+            #[cfg(feature = "compress-gzip")]
+            Decompressor::Gzip(ref mut d) => d.write_all(chunk).and_then(|_| d.flush()),
+            #[cfg(feature = "compress-gzip")]
+            Decompressor::Deflate(ref mut d) => d.write_all(chunk).and_then(|_| d.flush()),
+            #[cfg(feature = "compress-brotli")]
+            Decompressor::Brotli(ref mut d) => d.write_all(chunk).and_then(|_| d.flush()),
+            #[cfg(feature = "compress-zstd")]
+            Decompressor::Zstd(ref mut d) => d.write_all(chunk).and_then(|_| d.flush()),
+        };
+        res.map_err(|e| {
+            self.output()
+                .exceeded
+                .take()
+                .unwrap_or_else(|| Self::error(e))
+        })
+    }
+
+    /// Decompress what is left of the body and hand out the decompressed body.
+    ///
+    /// A body ending before its compressed stream does is malformed, it is not taken for a
+    /// shorter body.
+    pub(crate) fn finish(self) -> Result<BytesMut, PayloadError> {
+        let (res, mut output): (io::Result<()>, Output) = match self.0 {
+            #[cfg(feature = "compress-gzip")]
+            Decompressor::Gzip(mut d) => (d.try_finish(), std::mem::take(d.get_mut())),
+            #[cfg(feature = "compress-gzip")]
+            Decompressor::Deflate(mut d) => (d.try_finish(), std::mem::take(d.get_mut())),
+            #[cfg(feature = "compress-brotli")]
+            // `into_inner` closes the stream and hands the writer back either way.
+            Decompressor::Brotli(d) => match d.into_inner() {
+                Ok(output) => (Ok(()), output),
+                Err(output) => (
+                    Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "incomplete brotli stream",
+                    )),
+                    output,
+                ),
+            },
+            #[cfg(feature = "compress-zstd")]
+            Decompressor::Zstd(mut d) => (d.finish(), std::mem::take(d.writer_mut())),
+        };
+        res.map_err(|e| output.exceeded.take().unwrap_or_else(|| Self::error(e)))?;
+        Ok(output.buf)
+    }
+
+    fn output(&mut self) -> &mut Output {
+        match self.0 {
+            #[cfg(feature = "compress-gzip")]
+            Decompressor::Gzip(ref mut d) => d.get_mut(),
+            #[cfg(feature = "compress-gzip")]
+            Decompressor::Deflate(ref mut d) => d.get_mut(),
+            #[cfg(feature = "compress-brotli")]
+            Decompressor::Brotli(ref mut d) => d.get_mut(),
+            #[cfg(feature = "compress-zstd")]
+            Decompressor::Zstd(ref mut d) => d.writer_mut(),
+        }
+    }
+
+    /// A body that fails to decompress is malformed.
+    fn error(err: io::Error) -> PayloadError {
+        PayloadError::Payload(error::PayloadError::Io(err))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ContentDecoder;
+    use crate::{PayloadError, RicksponseConfig};
+    use actix_http::header::{HeaderMap, HeaderValue, CONTENT_ENCODING};
+
+    /// The content encodings of the enabled compression features.
+    #[cfg(any(
+        feature = "compress-brotli",
+        feature = "compress-gzip",
+        feature = "compress-zstd"
+    ))]
+    fn encodings() -> impl Iterator<Item = &'static str> {
+        [
+            (cfg!(feature = "compress-gzip"), "gzip"),
+            (cfg!(feature = "compress-gzip"), "deflate"),
+            (cfg!(feature = "compress-brotli"), "br"),
+            (cfg!(feature = "compress-zstd"), "zstd"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, encoding)| enabled.then_some(encoding))
+    }
+
+    #[cfg(any(
+        feature = "compress-brotli",
+        feature = "compress-gzip",
+        feature = "compress-zstd"
+    ))]
+    fn compress(encoding: &str, data: &[u8]) -> Vec<u8> {
+        #[cfg(any(feature = "compress-brotli", feature = "compress-gzip"))]
+        use std::io::Write;
+
+        match encoding {
+            #[cfg(feature = "compress-gzip")]
+            "gzip" => {
+                let mut e = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+                e.write_all(data).unwrap();
+                e.finish().unwrap()
+            }
+            #[cfg(feature = "compress-gzip")]
+            "deflate" => {
+                let mut e =
+                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+                e.write_all(data).unwrap();
+                e.finish().unwrap()
+            }
+            #[cfg(feature = "compress-brotli")]
+            "br" => {
+                let mut e = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+                e.write_all(data).unwrap();
+                e.into_inner()
+            }
+            #[cfg(feature = "compress-zstd")]
+            "zstd" => zstd::stream::encode_all(data, 19).unwrap(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn decoder(encoding: &str, config: &RicksponseConfig) -> Result<ContentDecoder, PayloadError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_str(encoding).unwrap());
+        ContentDecoder::from_headers(&headers, config).map(Option::unwrap)
+    }
+
+    #[cfg(any(
+        feature = "compress-brotli",
+        feature = "compress-gzip",
+        feature = "compress-zstd"
+    ))]
+    fn decompress(
+        encoding: &str,
+        data: &[u8],
+        config: &RicksponseConfig,
+    ) -> Result<Vec<u8>, PayloadError> {
+        let mut decoder = decoder(encoding, config)?;
+        for chunk in compress(encoding, data).chunks(100) {
+            decoder.feed(chunk)?;
+        }
+        decoder.finish().map(|b| b.to_vec())
+    }
+
+    #[cfg(any(
+        feature = "compress-brotli",
+        feature = "compress-gzip",
+        feature = "compress-zstd"
+    ))]
+    #[test]
+    fn test_decompress() {
+        let data = r#"{"name":"Rubber Bullet","title":"The Bullet"}"#.repeat(100);
+        for encoding in encodings() {
+            let decompressed = decompress(encoding, data.as_bytes(), &RicksponseConfig::default());
+            assert_eq!(decompressed.unwrap(), data.as_bytes(), "{}", encoding);
+        }
+    }
+
+    #[test]
+    fn test_not_encoded() {
+        let config = RicksponseConfig::default();
+        assert!(ContentDecoder::from_headers(&HeaderMap::new(), &config)
+            .unwrap()
+            .is_none());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("identity"));
+        assert!(ContentDecoder::from_headers(&headers, &config)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_unsupported_encoding() {
+        assert!(matches!(
+            decoder("compress", &RicksponseConfig::default()),
+            Err(PayloadError::ContentEncoding(e)) if e == "compress"
+        ));
+    }
+
+    #[cfg(feature = "compress-gzip")]
+    #[test]
+    fn test_corrupt_body() {
+        let mut decoder = decoder("gzip", &RicksponseConfig::default()).unwrap();
+        let res = decoder
+            .feed(b"definitely not gzip")
+            .and_then(|_| decoder.finish().map(|_| ()));
+        assert!(matches!(res, Err(PayloadError::Payload(_))));
+    }
+
+    #[cfg(any(
+        feature = "compress-brotli",
+        feature = "compress-gzip",
+        feature = "compress-zstd"
+    ))]
+    #[test]
+    fn test_truncated_body() {
+        let data = r#"{"name":"Rubber Bullet","title":"The Bullet"}"#.repeat(100);
+        for encoding in encodings() {
+            let compressed = compress(encoding, data.as_bytes());
+            let mut decoder = decoder(encoding, &RicksponseConfig::default()).unwrap();
+            let res = decoder
+                .feed(&compressed[..compressed.len() - 4])
+                .and_then(|_| decoder.finish().map(|_| ()));
+            assert!(matches!(res, Err(PayloadError::Payload(_))), "{}", encoding);
+        }
+    }
+
+    #[cfg(any(
+        feature = "compress-brotli",
+        feature = "compress-gzip",
+        feature = "compress-zstd"
+    ))]
+    #[test]
+    fn test_decompressed_limit() {
+        let data = vec![b'a'; 1 << 20];
+        for encoding in encodings() {
+            let config = RicksponseConfig::default()
+                .decompressed_limit(1 << 16)
+                .compression_ratio_limit(usize::MAX);
+            assert!(
+                matches!(
+                    decompress(encoding, &data, &config),
+                    Err(PayloadError::DecompressedOverflow { limit: 65536 })
+                ),
+                "{}",
+                encoding
+            );
+        }
+    }
+
+    #[cfg(any(
+        feature = "compress-brotli",
+        feature = "compress-gzip",
+        feature = "compress-zstd"
+    ))]
+    #[test]
+    fn test_compression_ratio_limit() {
+        let data = vec![b'a'; 1 << 20];
+        for encoding in encodings() {
+            assert!(
+                matches!(
+                    decompress(encoding, &data, &RicksponseConfig::default()),
+                    Err(PayloadError::CompressionRatio { limit: 100 })
+                ),
+                "{}",
+                encoding
+            );
+        }
+
+        let config = RicksponseConfig::default().compression_ratio_limit(usize::MAX);
+        for encoding in encodings() {
+            assert_eq!(decompress(encoding, &data, &config).unwrap(), data);
+        }
+    }
+}
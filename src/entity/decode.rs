@@ -0,0 +1,234 @@
+use crate::entity::payload_error::PayloadError;
+use serde::de::{Deserialize, DeserializeOwned, Deserializer};
+use serde_path_to_error::Segment;
+use simple_serde::{ContentType, Decoded, SimpleDecoder};
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter};
+
+thread_local! {
+    /// The pointer to the value that failed to decode, handed out of the format's deserializer.
+    static POINTER: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Where in a payload decoding failed.
+///
+/// The pointer is known for every format, the line and column only for text formats reporting
+/// them, the byte offset only for binary formats reporting it.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorLocation {
+    /// JSON Pointer (RFC 6901) to the value that failed to decode, empty for the whole payload.
+    pub pointer: String,
+    /// One-based line.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// One-based column.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    /// Offset in bytes from the start of the payload.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+}
+
+/// A payload that failed to decode, see `PayloadError::Deserialize`.
+#[derive(Debug)]
+pub struct DecodeError {
+    pub error: simple_serde::Error,
+    pub location: ErrorLocation,
+}
+
+impl DecodeError {
+    fn new(error: simple_serde::Error, pointer: String) -> Self {
+        let (line, column, offset) = position(&error);
+        DecodeError {
+            error,
+            location: ErrorLocation {
+                pointer,
+                line,
+                column,
+                offset,
+            },
+        }
+    }
+}
+
+impl From<simple_serde::Error> for DecodeError {
+    fn from(error: simple_serde::Error) -> Self {
+        Self::new(error, String::new())
+    }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.location.pointer.as_str() {
+            "" => write!(f, "{}", self.error),
+            pointer => write!(f, "{} (at {})", self.error, pointer),
+        }
+    }
+}
+
+/// The line, column and byte offset reported by the format, where it reports them.
+fn position(error: &simple_serde::Error) -> (Option<usize>, Option<usize>, Option<usize>) {
+    use simple_serde::Error;
+    match error {
+        // serde_json reports line 0 for errors without a position.
+        Error::JsonError(e) if e.line() > 0 => (Some(e.line()), Some(e.column()), None),
+        Error::Json5Error(simple_serde::prelude::json5::Error::Message {
+            location: Some(location),
+            ..
+        }) => (Some(location.line), Some(location.column), None),
+        Error::YamlError(e) => match e.location() {
+            Some(location) => (
+                Some(location.line()),
+                Some(location.column()),
+                Some(location.index()),
+            ),
+            None => (None, None, None),
+        },
+        Error::TomlDeserializationFailure(e) => match e.line_col() {
+            Some((line, column)) => (Some(line + 1), Some(column + 1), None),
+            None => (None, None, None),
+        },
+        Error::RonError(e) => (Some(e.position.line), Some(e.position.col), None),
+        Error::CborFailure(e) if e.offset() > 0 => (None, None, Some(e.offset() as usize)),
+        Error::ByteToUTF8ConversionFailure(e) => (None, None, Some(e.valid_up_to())),
+        _ => (None, None, None),
+    }
+}
+
+/// Decodes `T`, keeping track of the value being decoded for the `DecodeError`.
+struct Tracked<T>(T);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Tracked<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        serde_path_to_error::deserialize(deserializer)
+            .map(Tracked)
+            .map_err(|e| {
+                POINTER.with(|p| *p.borrow_mut() = Some(pointer(e.path())));
+                e.into_inner()
+            })
+    }
+}
+
+/// The path as JSON Pointer, `~` and `/` in keys escaped.
+fn pointer(path: &serde_path_to_error::Path) -> String {
+    path.iter()
+        .filter_map(|segment| match segment {
+            Segment::Seq { index } => Some(index.to_string()),
+            Segment::Map { key } => Some(key.replace('~', "~0").replace('/', "~1")),
+            Segment::Enum { variant } => Some(variant.replace('~', "~0").replace('/', "~1")),
+            Segment::Unknown => None,
+        })
+        .fold(String::new(), |pointer, segment| pointer + "/" + &segment)
+}
+
+/// Decode a payload as `content_type`, reporting where it failed when it fails.
+pub(crate) fn decode<T: DeserializeOwned>(
+    bytes: &[u8],
+    content_type: &ContentType,
+) -> Result<T, PayloadError> {
+    POINTER.with(|p| p.borrow_mut().take());
+    bytes
+        .decode(content_type)
+        .map(|d: Decoded<Tracked<T>>| d.into().0)
+        .map_err(|e| {
+            let pointer = POINTER.with(|p| p.borrow_mut().take()).unwrap_or_default();
+            PayloadError::Deserialize(Box::new(DecodeError::new(e, pointer)))
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, ErrorLocation};
+    use crate::{ApiError, PayloadError};
+    use simple_serde::ContentType;
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Line {
+        item: String,
+        quantity: u32,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Order {
+        customer: String,
+        lines: Vec<Line>,
+    }
+
+    fn location(body: &[u8], content_type: ContentType) -> ErrorLocation {
+        match decode::<Order>(body, &content_type) {
+            Err(PayloadError::Deserialize(e)) => e.location,
+            res => panic!("{:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_json_location() {
+        let body = br#"{
+  "customer": "rick",
+  "lines": [
+    {"item": "bullet", "quantity": 1},
+    {"item": "bullet", "quantity": -1}
+  ]
+}"#;
+        assert_eq!(
+            location(body, ContentType::Json),
+            ErrorLocation {
+                pointer: "/lines/1/quantity".to_string(),
+                line: Some(5),
+                column: Some(37),
+                offset: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_problem_details() {
+        let err = decode::<Order>(br#"{"customer": 7}"#, &ContentType::Json).unwrap_err();
+        assert_eq!(
+            err.details().unwrap(),
+            serde_json::json!({"pointer": "/customer", "line": 1, "column": 14})
+        );
+    }
+
+    #[test]
+    fn test_yaml_location() {
+        let body = b"customer: rick\nlines:\n  - item: bullet\n    quantity: many\n";
+        let location = location(body, ContentType::Yaml);
+        assert_eq!(location.pointer, "/lines/0/quantity");
+        assert_eq!(location.line, Some(4));
+    }
+
+    #[test]
+    fn test_toml_location() {
+        let body = b"customer = \"rick\"\n\n[[lines]]\nitem = \"bullet\"\nquantity = \"many\"\n";
+        let location = location(body, ContentType::Toml);
+        assert_eq!(location.pointer, "/lines/0/quantity");
+        assert_eq!(location.line, Some(5));
+    }
+
+    #[test]
+    fn test_cbor_location() {
+        let body = serde_cbor::to_vec(&serde_json::json!({
+            "customer": "rick",
+            "lines": [{"item": 7, "quantity": 1}],
+        }))
+        .unwrap();
+        assert_eq!(location(&body, ContentType::Cbor).pointer, "/lines/0/item");
+
+        let body = serde_cbor::to_vec(&serde_json::json!({"customer": "rick"})).unwrap();
+        let location = location(&body[..body.len() - 2], ContentType::Cbor);
+        assert_eq!(location.pointer, "/customer");
+        assert_eq!(location.offset, Some(body.len() - 2));
+    }
+
+    #[test]
+    fn test_pointer_escaping() {
+        type Map = std::collections::HashMap<String, std::collections::HashMap<String, String>>;
+        match decode::<Map>(br#"{"lines": {"a/b~c": 1}}"#, &ContentType::Json) {
+            Err(PayloadError::Deserialize(e)) => assert_eq!(e.location.pointer, "/lines/a~1b~0c"),
+            res => panic!("{:?}", res),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+/// Wraps response data together with its message, status code and meta data.
+///
+/// With the default field names a response is rendered as
+/// `{ "status": 200, "message": "OK", "data": ..., "meta": {...} }`. The message is left out when
+/// the response has none, and the meta data when it is empty. Error responses are never
+/// enveloped, they are rendered as problem details.
+///
+/// The envelope is enabled for all responses through `RicksponseConfig::envelope`, or for a
+/// single response through `Ricksponse::envelope`.
+///
+/// ```rust
+/// use ricksponse::{Envelope, RicksponseConfig};
+///
+/// let config = RicksponseConfig::default().envelope(Envelope::default().data("result"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    data: Cow<'static, str>,
+    message: Cow<'static, str>,
+    status: Cow<'static, str>,
+    meta: Cow<'static, str>,
+}
+
+impl Envelope {
+    /// Set the name of the field holding the data, `data` by default.
+    pub fn data(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.data = name.into();
+        self
+    }
+
+    /// Set the name of the field holding the message, `message` by default.
+    pub fn message(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.message = name.into();
+        self
+    }
+
+    /// Set the name of the field holding the status code, `status` by default.
+    pub fn status(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.status = name.into();
+        self
+    }
+
+    /// Set the name of the field holding the meta data, `meta` by default.
+    pub fn meta(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.meta = name.into();
+        self
+    }
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Envelope {
+            data: Cow::Borrowed("data"),
+            message: Cow::Borrowed("message"),
+            status: Cow::Borrowed("status"),
+            meta: Cow::Borrowed("meta"),
+        }
+    }
+}
+
+/// The data of a response in its envelope, ready to be encoded.
+pub(crate) struct Enveloped<'a, T> {
+    pub(crate) envelope: &'a Envelope,
+    pub(crate) data: &'a T,
+    pub(crate) message: Option<&'a str>,
+    pub(crate) status: u16,
+    pub(crate) meta: &'a Map<String, Value>,
+}
+
+impl<'a, T: Serialize> Serialize for Enveloped<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Plain values go first, formats such as TOML cannot have them follow a table.
+        let len = 2 + self.message.is_some() as usize + !self.meta.is_empty() as usize;
+        let mut map = serializer.serialize_map(Some(len))?;
+        map.serialize_entry(self.envelope.status.as_ref(), &self.status)?;
+        if let Some(message) = self.message {
+            map.serialize_entry(self.envelope.message.as_ref(), message)?;
+        }
+        map.serialize_entry(self.envelope.data.as_ref(), self.data)?;
+        if !self.meta.is_empty() {
+            map.serialize_entry(self.envelope.meta.as_ref(), self.meta)?;
+        }
+        map.end()
+    }
+}
@@ -1,28 +1,41 @@
-use crate::entity::payload_control::PayloadControl;
+use crate::entity::api_error::ApiError;
+use crate::entity::negotiation::MediaType;
+use crate::entity::payload_control::{DefaultControl, PayloadControl};
 use crate::entity::payload_error::PayloadError;
-use crate::entity::payload_future::PayloadFuture;
-use crate::error::Error;
+use crate::entity::payload_future::{FromPayload, PayloadFuture};
+use crate::entity::ricksponse::parts::Parts;
+use crate::entity::validation::{Validate, Validated, ValidationErrors};
 use crate::Ricksponse;
 use actix_http::body::BoxBody;
-use actix_web::{FromRequest, HttpRequest, HttpResponse, HttpResponseBuilder, Responder};
+use actix_http::header::TryIntoHeaderPair;
+use actix_web::cookie::Cookie;
+use actix_web::{FromRequest, HttpRequest, HttpResponse, Responder};
 use hateoas_response::{HateoasResource, Status};
-use http::StatusCode;
-use serde::de::{DeserializeOwned, Visitor};
-use serde::{Deserialize, Deserializer, Serialize};
-use simple_serde::{ContentType, SimpleEncoder};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::fmt::Debug;
-use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 
-#[derive(Serialize, Debug, PartialEq)]
-pub struct Hateoas<T>
+#[derive(Serialize, Debug)]
+pub struct Hateoas<T, C = DefaultControl>
 where
     T: Serialize + HateoasResource + DeserializeOwned,
 {
     inner: hateoas_response::Hateoas<T>,
+    #[serde(skip)]
+    parts: Parts<C>,
 }
 
-impl<'de, T> serde::Deserialize<'de> for Hateoas<T>
+impl<T, C> PartialEq for Hateoas<T, C>
+where
+    T: Serialize + HateoasResource + DeserializeOwned + PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl<'de, T, C> serde::Deserialize<'de> for Hateoas<T, C>
 where
     T: DeserializeOwned + Serialize + HateoasResource,
 {
@@ -32,18 +45,57 @@ where
     {
         serde::__private::Result::map(
             serde::Deserialize::deserialize(deserializer),
-            |transparent| Hateoas { inner: transparent },
+            |transparent| Hateoas {
+                inner: transparent,
+                parts: Parts::default(),
+            },
         )
     }
 }
 
-impl<T: Serialize + HateoasResource + DeserializeOwned> Hateoas<T> {
+impl<T: Serialize + HateoasResource + DeserializeOwned, C> Hateoas<T, C> {
     pub fn into_inner(self) -> hateoas_response::Hateoas<T> {
         self.inner
     }
+
+    pub fn parts(&self) -> &Parts<C> {
+        &self.parts
+    }
+
+    pub fn parts_mut(&mut self) -> &mut Parts<C> {
+        &mut self.parts
+    }
+
+    /// The media type of the request body this resource was extracted from.
+    pub fn media_type(&self) -> Option<&MediaType> {
+        self.parts.media_type()
+    }
+
+    /// Insert a header into the response, replacing any header of the same name.
+    pub fn header(mut self, header: impl TryIntoHeaderPair) -> Self {
+        self.parts.insert_header(header);
+        self
+    }
+
+    /// Insert several headers into the response, see `header`.
+    pub fn insert_headers<H: TryIntoHeaderPair>(
+        mut self,
+        headers: impl IntoIterator<Item = H>,
+    ) -> Self {
+        for header in headers {
+            self.parts.insert_header(header);
+        }
+        self
+    }
+
+    /// Add a cookie to the response.
+    pub fn cookie(mut self, cookie: Cookie<'static>) -> Self {
+        self.parts.add_cookie(cookie);
+        self
+    }
 }
 
-impl<T: Serialize + HateoasResource + DeserializeOwned> Deref for Hateoas<T> {
+impl<T: Serialize + HateoasResource + DeserializeOwned, C> Deref for Hateoas<T, C> {
     type Target = hateoas_response::Hateoas<T>;
 
     fn deref(&self) -> &Self::Target {
@@ -51,7 +103,7 @@ impl<T: Serialize + HateoasResource + DeserializeOwned> Deref for Hateoas<T> {
     }
 }
 
-impl<T: Serialize + HateoasResource + DeserializeOwned> DerefMut for Hateoas<T> {
+impl<T: Serialize + HateoasResource + DeserializeOwned, C> DerefMut for Hateoas<T, C> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.inner
     }
@@ -86,6 +138,7 @@ macro_rules! auto_impl_hateoas_response_wrapper {
             pub fn $konst(t: Option<T>) -> Self {
                 Self {
                     inner: hateoas_response::Hateoas::$konst(t),
+                    parts: Parts::default(),
                 }
             }
             )+
@@ -276,90 +329,68 @@ auto_impl_hateoas_response_wrapper! {
     (NETWORK_AUTHENTICATION_REQUIRED);
 }
 
-impl<T: Serialize + HateoasResource + DeserializeOwned + Default> From<Hateoas<T>>
-    for Ricksponse<hateoas_response::Hateoas<T>>
+impl<T: Serialize + HateoasResource + DeserializeOwned + Default, C> From<Hateoas<T, C>>
+    for Ricksponse<hateoas_response::Hateoas<T>, C>
 {
-    fn from(r: Hateoas<T>) -> Self {
+    fn from(r: Hateoas<T, C>) -> Self {
         Ricksponse::Data {
-            http_code: r
-                .status()
-                .as_ref()
-                .and_then(|t| t.http_status_code().clone()),
+            http_code: r.status().as_ref().and_then(|t| *t.http_status_code()),
             message: r.status().as_ref().and_then(|t| t.message().clone()),
             data: r.inner,
+            parts: r.parts,
         }
     }
 }
 
-impl<T: Serialize + HateoasResource + DeserializeOwned + Default> Responder for Hateoas<T> {
+impl<T: Serialize + HateoasResource + DeserializeOwned + Default, C> Responder for Hateoas<T, C> {
     type Body = BoxBody;
 
     fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
-        let mut content_type_collection = req
-            .headers()
-            .get_all("Accept")
-            .filter_map(|h| ContentType::try_from(h).ok())
-            .collect::<Vec<ContentType>>();
-        if content_type_collection.is_empty() {
-            content_type_collection = vec![ContentType::Json];
-        }
-
-        let ricksponse: Ricksponse<hateoas_response::Hateoas<T>> = self.into();
-        match ricksponse {
-            Ricksponse::Data {
-                data, http_code, ..
-            } => {
-                let response_code = match http_code {
-                    Some(code) => StatusCode::from_u16(code).unwrap_or(StatusCode::OK),
-                    None => StatusCode::OK,
-                };
-                content_type_collection.reverse();
-                content_type_collection
-                    .pop()
-                    .ok_or_else(|| HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR))
-                    .and_then(|content_type| {
-                        data.encode(&content_type)
-                            .map(|t| {
-                                HttpResponseBuilder::new(response_code)
-                                    .content_type(content_type)
-                                    .body(t.to_vec())
-                            })
-                            .map_err(|_| HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR))
-                    })
-                    .unwrap_or_else(|e| e)
-            }
-            Ricksponse::Error { http_code, .. } => match http_code {
-                Some(code) => HttpResponse::new(
-                    StatusCode::from_u16(code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
-                ),
-                None => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
-            },
-        }
+        Ricksponse::from(self).respond_to(req)
     }
 }
 
-impl<T: Serialize + HateoasResource + DeserializeOwned + Default>
-    From<Result<hateoas_response::Hateoas<T>, PayloadError>> for Hateoas<T>
+impl<T: Serialize + HateoasResource + DeserializeOwned + Default, C, E: ApiError>
+    From<Result<hateoas_response::Hateoas<T>, E>> for Hateoas<T, C>
 {
-    fn from(res: Result<hateoas_response::Hateoas<T>, PayloadError>) -> Self {
+    fn from(res: Result<hateoas_response::Hateoas<T>, E>) -> Self {
         match res {
-            Ok(inner) => Hateoas { inner },
+            Ok(inner) => Hateoas {
+                inner,
+                parts: Parts::default(),
+            },
             Err(e) => {
-                let mut status = Status::INTERNAL_SERVER_ERROR();
-                *status.message_mut() = Some(format!("{:?}", e));
+                let status = Status::new(e.message().as_deref(), None, Some(e.status()), None);
                 let inner: hateoas_response::Hateoas<T> =
                     hateoas_response::Hateoas::new(None, None, Some(status));
-                Hateoas { inner }
+                Hateoas {
+                    inner,
+                    parts: Parts::default(),
+                }
             }
         }
     }
 }
-impl<T> FromRequest for Hateoas<T>
+
+impl<T: Serialize + HateoasResource + DeserializeOwned + Default, C>
+    FromPayload<hateoas_response::Hateoas<T>> for Hateoas<T, C>
+{
+    fn from_payload(
+        res: Result<hateoas_response::Hateoas<T>, PayloadError>,
+        media_type: Option<MediaType>,
+    ) -> Result<Self, PayloadError> {
+        let mut hateoas = Self::from(res);
+        hateoas.parts.media_type = media_type;
+        Ok(hateoas)
+    }
+}
+impl<T, C> FromRequest for Hateoas<T, C>
 where
-    T: Serialize + DeserializeOwned + HateoasResource + PayloadControl + Default,
+    T: Serialize + DeserializeOwned + HateoasResource + Default,
+    C: PayloadControl,
 {
-    type Error = Error;
-    type Future = PayloadFuture<T, hateoas_response::Hateoas<T>, Hateoas<T>>;
+    type Error = actix_web::Error;
+    type Future = PayloadFuture<C, hateoas_response::Hateoas<T>, Hateoas<T, C>>;
 
     #[inline]
     fn from_request(req: &HttpRequest, payload: &mut actix_http::Payload) -> Self::Future {
@@ -367,11 +398,40 @@ where
     }
 }
 
+impl<T, C> FromRequest for Hateoas<T, Validated<C>>
+where
+    T: Serialize + DeserializeOwned + HateoasResource + Default + Validate,
+    C: PayloadControl,
+{
+    type Error = actix_web::Error;
+    type Future = PayloadFuture<C, hateoas_response::Hateoas<T>, Hateoas<T, Validated<C>>>;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, payload: &mut actix_http::Payload) -> Self::Future {
+        PayloadFuture::validated(req.clone(), payload)
+    }
+}
+
+/// Validates the content of the spec, the fields are reported below `spec.content`.
+impl<T> Validate for hateoas_response::Hateoas<T>
+where
+    T: Serialize + HateoasResource + Validate,
+{
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if let Some(spec) = &self.spec {
+            errors.nest("spec.content", spec.content().validate());
+        }
+        errors.into_result()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::hateoas::prelude;
     use crate::hateoas::Hateoas;
-    use actix_web::{http::header, test, web, App};
+    use actix_web::{http::header, test, web, App, Responder};
+    use http::StatusCode;
     use serde_json;
     use std::ops::Deref;
 
@@ -399,10 +459,15 @@ mod test {
         const URL_PATH_SEGMENT: &'static str = "";
     }
 
-    const RICKSPONSE_1: &str = r##"
-
-
-    "##;
+    impl crate::Validate for RubberBullet {
+        fn validate(&self) -> Result<(), crate::ValidationErrors> {
+            let mut errors = crate::ValidationErrors::new();
+            if self.name.is_empty() {
+                errors.add("name", "must not be empty");
+            }
+            errors.into_result()
+        }
+    }
 
     #[actix_web::test]
     async fn test_hateoas_string() {
@@ -422,10 +487,8 @@ mod test {
         let res = test::call_service(&app, req).await;
         let result = test::read_body(res).await;
 
-        let raw_str = std::str::from_utf8(&*result).unwrap();
-        println!("{}", raw_str);
+        let raw_str = std::str::from_utf8(&result).unwrap();
         let content = serde_json::from_str::<Hateoas<String>>(raw_str).unwrap();
-        println!("{:#?}", content);
         assert_eq!(content, Hateoas::OK(Some("welcome!".to_string())));
     }
 
@@ -449,10 +512,8 @@ mod test {
         let res = test::call_service(&app, req).await;
         let result = test::read_body(res).await;
 
-        let raw_str = std::str::from_utf8(&*result).unwrap();
-        println!("{}", raw_str);
+        let raw_str = std::str::from_utf8(&result).unwrap();
         let content = serde_json::from_str::<Hateoas<RubberBullet>>(raw_str).unwrap();
-        println!("{:#?}", content);
         assert_eq!(content, response);
     }
 
@@ -469,4 +530,39 @@ mod test {
             )
         );
     }
+
+    #[actix_web::test]
+    async fn test_hateoas_response_headers() {
+        let req = test::TestRequest::default().to_http_request();
+
+        let res = Hateoas::CREATED(Some(RubberBullet::default()))
+            .header((header::LOCATION, "/bullets/1"))
+            .respond_to(&req);
+
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.headers().get(header::LOCATION).unwrap(), "/bullets/1");
+    }
+
+    #[actix_web::test]
+    async fn test_hateoas_validated() {
+        let app = test::init_service(App::new().route(
+            "/",
+            web::post().to(|_: Hateoas<RubberBullet, crate::Validated>| async { "ok" }),
+        ))
+        .await;
+
+        let req = test::TestRequest::post()
+            .insert_header(header::ContentType::json())
+            .set_payload(
+                r#"{"apiVersion":"","kind":"","metadata":null,"status":null,
+                "spec":{"content":{"name":"","title":"","chapter":""},"rel":null}}"#,
+            )
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let problem: serde_json::Value =
+            serde_json::from_slice(&test::read_body(res).await).unwrap();
+        assert_eq!(problem["details"][0]["field"], "spec.content.name");
+    }
 }
@@ -1,7 +1,18 @@
+pub mod api_error;
+pub mod conditional;
+pub mod content_decoder;
+pub mod decode;
+pub mod envelope;
 #[cfg(feature = "hateoas")]
 pub mod hateoas;
+pub mod negotiation;
 pub mod payload_body;
 pub mod payload_control;
 pub mod payload_error;
 pub mod payload_future;
+pub mod payload_stream;
+pub mod problem_details;
 pub mod ricksponse;
+pub mod ricksponse_config;
+pub mod strict;
+pub mod validation;
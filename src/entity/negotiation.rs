@@ -0,0 +1,634 @@
+use crate::error::Error;
+use actix_http::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, VARY};
+use simple_serde::ContentType;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Every content type the responders are able to produce, in order of server preference.
+///
+/// When a client ranks two formats equally (`*/*`, or no `Accept` header at all) the one listed
+/// first wins.
+pub const SUPPORTED_CONTENT_TYPES: &[ContentType] = &[
+    ContentType::Json,
+    ContentType::Yaml,
+    ContentType::Toml,
+    ContentType::Json5,
+    ContentType::Ron,
+    ContentType::Lexpr,
+    ContentType::Url,
+    ContentType::Cbor,
+    ContentType::MessagePack,
+    ContentType::Bson,
+    ContentType::FlexBuffers,
+    ContentType::Postcard,
+    ContentType::Pickle,
+];
+
+/// The request headers `negotiate` looks at, a negotiated response varies on them.
+pub const NEGOTIATION_HEADERS: &[HeaderName] = &[ACCEPT];
+
+/// Highest weight a media range can carry, `q=1`.
+const MAX_QUALITY: u16 = 1000;
+
+/// All media types `simple_serde` maps onto the given content type, the canonical one first.
+pub fn media_types(content_type: &ContentType) -> &'static [&'static str] {
+    match content_type {
+        ContentType::Bson => &["application/x-bson", "application/bson"],
+        ContentType::Cbor => &["application/x-cbor", "application/cbor"],
+        ContentType::FlexBuffers => &["application/x-flexbuffers", "application/flexbuffers"],
+        ContentType::Json => &["application/json", "application/x-json"],
+        ContentType::Json5 => &["application/json5", "application/x-json5"],
+        ContentType::Lexpr => &["application/x-lexpr", "application/lexpr"],
+        ContentType::MessagePack => &["application/x-messagepack", "application/messagepack"],
+        ContentType::Pickle => &["application/x-pickle", "application/pickle"],
+        ContentType::Postcard => &["application/x-postcard", "application/postcard"],
+        ContentType::Ron => &["application/ron", "application/x-ron"],
+        ContentType::Toml => &["application/toml", "application/x-toml"],
+        ContentType::Url => &["application/x-url", "application/url"],
+        ContentType::Yaml => &["application/yaml", "application/x-yaml"],
+    }
+}
+
+/// The canonical media type of each content type, as advertised to clients sending a payload in
+/// a content type that is not accepted.
+pub(crate) fn accepted_media_types(content_types: &[ContentType]) -> Vec<&'static str> {
+    content_types.iter().map(|c| media_types(c)[0]).collect()
+}
+
+/// The RFC 6839 structured syntax suffix of a content type, if it has one registered.
+pub fn structured_syntax_suffix(content_type: &ContentType) -> Option<&'static str> {
+    match content_type {
+        ContentType::Json => Some("json"),
+        ContentType::Yaml => Some("yaml"),
+        ContentType::Cbor => Some("cbor"),
+        _ => None,
+    }
+}
+
+/// Resolves a media type to the supported content type able to decode/encode it.
+///
+/// The essence of the media type is matched first, after which vendor types such as
+/// `application/vnd.acme.order+json` are resolved through their structured syntax suffix.
+pub fn content_type_for(media_type: &MediaType) -> Option<&'static ContentType> {
+    let essence = media_type.essence();
+    SUPPORTED_CONTENT_TYPES
+        .iter()
+        .find(|c| media_types(c).contains(&essence.as_str()))
+        .or_else(|| {
+            media_type.suffix().and_then(|suffix| {
+                SUPPORTED_CONTENT_TYPES
+                    .iter()
+                    .find(|c| structured_syntax_suffix(c) == Some(suffix))
+            })
+        })
+}
+
+/// Parses a `Content-Type` style header value into the media type and its content type.
+///
+/// Bare format names such as `yaml`, which `simple_serde` has always accepted, resolve to the
+/// canonical media type of the format.
+pub fn resolve(value: &str) -> Option<(&'static ContentType, MediaType)> {
+    match MediaType::from_str(value) {
+        Ok(media_type) => content_type_for(&media_type).map(|c| (c, media_type)),
+        Err(_) => ContentType::try_from(value.trim()).ok().and_then(|c| {
+            let media_type = canonical_media_type(&c);
+            content_type_for(&media_type).map(|c| (c, media_type))
+        }),
+    }
+}
+
+fn canonical_media_type(content_type: &ContentType) -> MediaType {
+    MediaType::from_str(media_types(content_type)[0])
+        .expect("canonical media types are valid media types")
+}
+
+/// A parsed `type/subtype; name=value` media type.
+///
+/// Type, subtype and parameter names are compared case-insensitively and are therefore stored in
+/// lower case. Parameter values keep their original case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaType {
+    main_type: String,
+    sub_type: String,
+    params: Vec<(String, String)>,
+}
+
+impl MediaType {
+    pub fn main_type(&self) -> &str {
+        &self.main_type
+    }
+
+    pub fn sub_type(&self) -> &str {
+        &self.sub_type
+    }
+
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The RFC 6839 structured syntax suffix, eg. `json` for `application/vnd.acme.order+json`.
+    pub fn suffix(&self) -> Option<&str> {
+        self.sub_type
+            .rsplit_once('+')
+            .map(|(_, suffix)| suffix)
+            .filter(|suffix| !suffix.is_empty())
+    }
+
+    /// The media type without any parameters, eg. `application/json`.
+    pub fn essence(&self) -> String {
+        format!("{}/{}", self.main_type, self.sub_type)
+    }
+}
+
+impl FromStr for MediaType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = split_unquoted(s, ';').into_iter();
+        let essence = segments.next().unwrap_or_default();
+        let (main_type, sub_type) = essence
+            .split_once('/')
+            .map(|(main, sub)| (main.trim(), sub.trim()))
+            .filter(|(main, sub)| is_token(main) && is_token(sub))
+            .ok_or_else(|| Error::InvalidMediaType(s.to_string()))?;
+        let params = segments
+            .filter(|segment| !segment.trim().is_empty())
+            .map(|segment| {
+                segment
+                    .split_once('=')
+                    .map(|(key, value)| (key.trim(), value.trim()))
+                    .filter(|(key, _)| is_token(key))
+                    .map(|(key, value)| (key.to_ascii_lowercase(), unquote(value)))
+                    .ok_or_else(|| Error::InvalidMediaType(s.to_string()))
+            })
+            .collect::<Result<Vec<(String, String)>, Error>>()?;
+        Ok(MediaType {
+            main_type: main_type.to_ascii_lowercase(),
+            sub_type: sub_type.to_ascii_lowercase(),
+            params,
+        })
+    }
+}
+
+impl Display for MediaType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.main_type, self.sub_type)?;
+        for (key, value) in &self.params {
+            if is_token(value) {
+                write!(f, "; {}={}", key, value)?;
+            } else {
+                write!(
+                    f,
+                    "; {}=\"{}\"",
+                    key,
+                    value.replace('\\', "\\\\").replace('"', "\\\"")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A single entry of an `Accept` header, a media type (possibly a wildcard) with its weight.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaRange {
+    media_type: MediaType,
+    quality: u16,
+}
+
+impl MediaRange {
+    pub fn media_type(&self) -> &MediaType {
+        &self.media_type
+    }
+
+    /// The `q` weight of the range in thousandths, `1000` being `q=1`.
+    pub fn quality(&self) -> u16 {
+        self.quality
+    }
+
+    /// Whether the range matches any subtype, `type/*` or `*/*`.
+    pub fn is_wildcard(&self) -> bool {
+        self.media_type.sub_type == "*"
+    }
+
+    /// How specific this range is when it matches `media_type`, `None` when it does not match.
+    ///
+    /// `*/*` < `type/*` < `type/subtype` < `type/subtype` with matching parameters.
+    pub fn specificity(&self, media_type: &MediaType) -> Option<u8> {
+        let range = &self.media_type;
+        match (range.main_type.as_str(), range.sub_type.as_str()) {
+            ("*", "*") => Some(0),
+            (main, "*") if main == media_type.main_type => Some(1),
+            (main, sub) if main == media_type.main_type && sub == media_type.sub_type => {
+                let params_match = !range.params.is_empty()
+                    && range
+                        .params
+                        .iter()
+                        .all(|(key, value)| media_type.param(key) == Some(value.as_str()));
+                Some(if params_match { 3 } else { 2 })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for MediaRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut media_type = MediaType::from_str(s)?;
+        if media_type.main_type == "*" && media_type.sub_type != "*" {
+            return Err(Error::InvalidMediaType(s.to_string()));
+        }
+        // Everything from the `q` parameter onwards are accept-extensions and not part of the
+        // media range.
+        let mut quality = MAX_QUALITY;
+        if let Some(position) = media_type.params.iter().position(|(key, _)| key == "q") {
+            quality = parse_quality(&media_type.params[position].1)
+                .ok_or_else(|| Error::InvalidMediaType(s.to_string()))?;
+            media_type.params.truncate(position);
+        }
+        Ok(MediaRange {
+            media_type,
+            quality,
+        })
+    }
+}
+
+/// Collects every media range of every `Accept` header in the request.
+///
+/// Entries that can not be parsed are skipped. Bare format names such as `yaml`, which
+/// `simple_serde` has always accepted, are mapped onto their canonical media type.
+pub fn accept_ranges(headers: &HeaderMap) -> Vec<MediaRange> {
+    headers
+        .get_all(ACCEPT)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| split_unquoted(value, ','))
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            MediaRange::from_str(entry).ok().or_else(|| {
+                let (name, rest) = entry.split_once(';').unwrap_or((entry, ""));
+                ContentType::try_from(name.trim()).ok().and_then(|c| {
+                    MediaRange::from_str(&format!("{};{}", media_types(&c)[0], rest)).ok()
+                })
+            })
+        })
+        .collect()
+}
+
+/// The weight the client gave `media_type`, taken from the most specific matching range.
+pub fn quality_of(ranges: &[MediaRange], media_type: &MediaType) -> Option<u16> {
+    weigh(ranges, media_type).map(|(_, quality)| quality)
+}
+
+/// Specificity and weight of the most specific range matching `media_type`.
+fn weigh(ranges: &[MediaRange], media_type: &MediaType) -> Option<(u8, u16)> {
+    ranges
+        .iter()
+        .filter_map(|range| range.specificity(media_type).map(|s| (s, range.quality)))
+        .max_by_key(|(specificity, _)| *specificity)
+}
+
+/// Adds `names` to the `Vary` header, keeping the names already listed and skipping duplicates.
+///
+/// A `Vary: *` stays as it is, the response varies on everything already.
+pub(crate) fn append_vary<'a>(headers: &mut HeaderMap, names: impl IntoIterator<Item = &'a str>) {
+    let mut vary: Vec<String> = headers
+        .get_all(VARY)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+    for name in names {
+        if !vary
+            .iter()
+            .any(|v| v == "*" || v.eq_ignore_ascii_case(name))
+        {
+            vary.push(name.to_string());
+        }
+    }
+    if vary.iter().any(|v| v == "*") {
+        vary = vec!["*".to_string()];
+    }
+    if let Ok(value) = HeaderValue::from_str(&vary.join(", ")) {
+        headers.insert(VARY, value);
+    }
+}
+
+/// The outcome of content negotiation, the format to encode with and the media type to announce.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Negotiated<'a> {
+    content_type: &'a ContentType,
+    media_type: MediaType,
+}
+
+impl<'a> Negotiated<'a> {
+    pub fn content_type(&self) -> &'a ContentType {
+        self.content_type
+    }
+
+    /// The media type for the `Content-Type` header. This is the requested vendor media type,
+    /// parameters included, when the client asked for one.
+    pub fn media_type(&self) -> &MediaType {
+        &self.media_type
+    }
+}
+
+/// Picks the content type to respond with according to RFC 7231 section 5.3.2.
+///
+/// Each of the `supported` content types is weighted by the most specific media range matching
+/// it. The highest weight wins and ties are broken by the order of `supported`. A weight of `q=0`
+/// means "not acceptable". Without any usable `Accept` header the first supported type is used.
+///
+/// Requested vendor media types with a structured syntax suffix, such as
+/// `application/vnd.acme.order+json; version=2`, are answered as-is using the format of their
+/// suffix.
+pub fn negotiate<'a>(
+    headers: &HeaderMap,
+    supported: &'a [ContentType],
+) -> crate::Result<Negotiated<'a>> {
+    let ranges = accept_ranges(headers);
+    if ranges.is_empty() {
+        return supported
+            .first()
+            .map(|content_type| Negotiated {
+                content_type,
+                media_type: canonical_media_type(content_type),
+            })
+            .ok_or(Error::NoAcceptableContentType);
+    }
+
+    let mut best: Option<(u16, Negotiated)> = None;
+    for content_type in supported {
+        // An alias only counts when it is matched more specifically than the other aliases, so
+        // `application/json;q=0, */*` does not sneak JSON back in as `application/x-json`.
+        let quality = media_types(content_type)
+            .iter()
+            .filter_map(|m| MediaType::from_str(m).ok())
+            .filter_map(|m| weigh(&ranges, &m))
+            .max()
+            .map(|(_, quality)| quality)
+            .unwrap_or(0);
+        // The most preferred vendor type the client asked for that this format can produce
+        // through its structured syntax suffix.
+        let vendor = ranges
+            .iter()
+            .filter(|range| !range.is_wildcard() && range.quality > 0)
+            .filter(|range| {
+                !media_types(content_type).contains(&range.media_type.essence().as_str())
+            })
+            .filter(|range| {
+                range.media_type.suffix().is_some()
+                    && range.media_type.suffix() == structured_syntax_suffix(content_type)
+            })
+            .fold(None, |best: Option<&MediaRange>, range| match best {
+                Some(b) if b.quality >= range.quality => Some(b),
+                _ => Some(range),
+            });
+        let (candidate_quality, media_type) = match vendor {
+            Some(range) if range.quality >= quality => (range.quality, range.media_type.clone()),
+            _ if quality > 0 => (quality, canonical_media_type(content_type)),
+            _ => continue,
+        };
+        if best
+            .as_ref()
+            .map(|(q, _)| candidate_quality > *q)
+            .unwrap_or(true)
+        {
+            let negotiated = Negotiated {
+                content_type,
+                media_type,
+            };
+            best = Some((candidate_quality, negotiated));
+        }
+    }
+    best.map(|(_, negotiated)| negotiated)
+        .ok_or(Error::NoAcceptableContentType)
+}
+
+fn parse_quality(s: &str) -> Option<u16> {
+    let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+    if fraction.len() > 3 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let millis = format!("{:0<3}", fraction).parse::<u16>().ok()?;
+    match whole {
+        "0" => Some(millis),
+        "1" if millis == 0 => Some(MAX_QUALITY),
+        _ => None,
+    }
+}
+
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+}
+
+fn unquote(s: &str) -> String {
+    match s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(inner) => {
+            let mut out = String::with_capacity(inner.len());
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => out.extend(chars.next()),
+                    c => out.push(c),
+                }
+            }
+            out
+        }
+        None => s.to_string(),
+    }
+}
+
+/// Splits on `separator` while leaving quoted strings intact.
+fn split_unquoted(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn accept(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(ACCEPT, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_parse_media_range() {
+        let range =
+            MediaRange::from_str("Application/JSON; charset=\"utf-8\"; q=0.5; ext=1").unwrap();
+        assert_eq!(range.media_type().essence(), "application/json");
+        assert_eq!(range.media_type().param("charset"), Some("utf-8"));
+        assert_eq!(range.media_type().params().len(), 1);
+        assert_eq!(range.quality(), 500);
+    }
+
+    #[test]
+    fn test_parse_media_range_failure() {
+        assert!(MediaRange::from_str("application").is_err());
+        assert!(MediaRange::from_str("*/json").is_err());
+        assert!(MediaRange::from_str("application/json; q=1.5").is_err());
+        assert!(MediaRange::from_str("application/json; q=0.1234").is_err());
+    }
+
+    #[test]
+    fn test_negotiate_without_accept() {
+        let headers = HeaderMap::new();
+        assert_eq!(
+            negotiate(&headers, SUPPORTED_CONTENT_TYPES)
+                .unwrap()
+                .content_type(),
+            &ContentType::Json
+        );
+    }
+
+    #[test]
+    fn test_negotiate_quality() {
+        let headers = accept(&["application/json;q=0.4, application/yaml;q=0.9"]);
+        assert_eq!(
+            negotiate(&headers, SUPPORTED_CONTENT_TYPES)
+                .unwrap()
+                .content_type(),
+            &ContentType::Yaml
+        );
+    }
+
+    #[test]
+    fn test_negotiate_multiple_headers_and_wildcards() {
+        let headers = accept(&["text/html", "application/*;q=0.2, application/x-cbor"]);
+        assert_eq!(
+            negotiate(&headers, SUPPORTED_CONTENT_TYPES)
+                .unwrap()
+                .content_type(),
+            &ContentType::Cbor
+        );
+        let headers = accept(&["text/html, */*;q=0.1"]);
+        assert_eq!(
+            negotiate(&headers, SUPPORTED_CONTENT_TYPES)
+                .unwrap()
+                .content_type(),
+            &ContentType::Json
+        );
+    }
+
+    #[test]
+    fn test_negotiate_excluded_by_zero_quality() {
+        let headers = accept(&["application/json;q=0, */*"]);
+        assert_eq!(
+            negotiate(&headers, SUPPORTED_CONTENT_TYPES)
+                .unwrap()
+                .content_type(),
+            &ContentType::Yaml
+        );
+    }
+
+    #[test]
+    fn test_negotiate_not_acceptable() {
+        let headers = accept(&["text/html, image/*"]);
+        assert!(matches!(
+            negotiate(&headers, SUPPORTED_CONTENT_TYPES),
+            Err(Error::NoAcceptableContentType)
+        ));
+    }
+
+    #[test]
+    fn test_negotiate_legacy_format_name() {
+        let headers = accept(&["yaml"]);
+        assert_eq!(
+            negotiate(&headers, SUPPORTED_CONTENT_TYPES)
+                .unwrap()
+                .content_type(),
+            &ContentType::Yaml
+        );
+    }
+
+    #[test]
+    fn test_negotiate_vendor_media_type() {
+        let headers =
+            accept(&["application/vnd.acme.order+json; version=2, application/json;q=0.5"]);
+        let negotiated = negotiate(&headers, SUPPORTED_CONTENT_TYPES).unwrap();
+        assert_eq!(negotiated.content_type(), &ContentType::Json);
+        assert_eq!(
+            negotiated.media_type().to_string(),
+            "application/vnd.acme.order+json; version=2"
+        );
+
+        let headers = accept(&["application/vnd.acme.order+yaml;q=0.2, application/json"]);
+        let negotiated = negotiate(&headers, SUPPORTED_CONTENT_TYPES).unwrap();
+        assert_eq!(negotiated.media_type().to_string(), "application/json");
+    }
+
+    #[test]
+    fn test_negotiate_unknown_suffix() {
+        let headers = accept(&["application/vnd.acme.order+zip"]);
+        assert!(negotiate(&headers, SUPPORTED_CONTENT_TYPES).is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let (content_type, media_type) = resolve("application/json; charset=utf-8").unwrap();
+        assert_eq!(content_type, &ContentType::Json);
+        assert_eq!(media_type.param("charset"), Some("utf-8"));
+
+        let (content_type, media_type) = resolve("application/vnd.acme.order+cbor").unwrap();
+        assert_eq!(content_type, &ContentType::Cbor);
+        assert_eq!(media_type.suffix(), Some("cbor"));
+
+        let (content_type, media_type) = resolve("yaml").unwrap();
+        assert_eq!(content_type, &ContentType::Yaml);
+        assert_eq!(media_type.essence(), "application/yaml");
+
+        assert!(resolve("text/html").is_none());
+    }
+
+    #[test]
+    fn test_append_vary() {
+        let mut headers = HeaderMap::new();
+        append_vary(&mut headers, ["Accept"]);
+        assert_eq!(headers.get(VARY).unwrap(), "Accept");
+
+        headers.insert(VARY, HeaderValue::from_static("accept, Origin"));
+        append_vary(&mut headers, ["Accept", "Accept-Language"]);
+        assert_eq!(
+            headers.get(VARY).unwrap(),
+            "accept, Origin, Accept-Language"
+        );
+
+        headers.insert(VARY, HeaderValue::from_static("*"));
+        append_vary(&mut headers, ["Accept"]);
+        assert_eq!(headers.get(VARY).unwrap(), "*");
+    }
+}
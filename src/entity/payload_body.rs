@@ -1,20 +1,25 @@
+use crate::entity::content_decoder::ContentDecoder;
+use crate::entity::decode::decode;
+use crate::entity::negotiation::{accepted_media_types, resolve, MediaType};
 use crate::entity::payload_control::PayloadControl;
 use crate::entity::payload_error::PayloadError;
+use crate::entity::ricksponse_config::RicksponseConfig;
 use crate::error::Error;
 use actix_http::Payload;
 use actix_web::HttpRequest;
 use bytes::BytesMut;
 use futures_core::Stream as _;
-use http::header::CONTENT_LENGTH;
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
 use serde::de::DeserializeOwned;
-use simple_serde::{ContentType, Decoded, SimpleDecoder};
+use simple_serde::ContentType;
 use std::future::Future;
 use std::marker::PhantomData;
-use std::ops::Deref;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
-const DEFAULT_LIMIT: usize = 41_943_040; // 40 mb
+/// Most bytes reserved for a body from its `Content-Length` before they arrive, the buffer grows
+/// beyond it as the body is read. A declared length alone must not pin memory.
+const MAX_PREALLOCATION: usize = 65_536;
 
 pub enum PayloadBody<T, O> {
     Error(Option<PayloadError>),
@@ -22,9 +27,14 @@ pub enum PayloadBody<T, O> {
         limit: usize,
         /// Length as reported by `Content-Length` header, if present.
         length: Option<usize>,
-        content_type: ContentType,
+        content_type: &'static ContentType,
+        /// The request media type as sent, eg. `application/vnd.acme.order+json; version=2`.
+        media_type: MediaType,
         payload: Payload,
         buf: BytesMut,
+        /// Bytes received, compressed when the body has a content encoding.
+        received: usize,
+        decoder: Option<ContentDecoder>,
         _res: PhantomData<T>,
         _payload_res: PhantomData<O>,
     },
@@ -33,84 +43,83 @@ pub enum PayloadBody<T, O> {
 impl<T, O> Unpin for PayloadBody<T, O> {}
 
 impl<T: DeserializeOwned, O: PayloadControl> PayloadBody<T, O> {
-    /// Create a new future to decode a JSON request payload.
+    /// Create a new future to decode a request payload.
+    ///
+    /// Limits and allowed content types are taken from the `RicksponseConfig` of the request,
+    /// unless overridden by the `PayloadControl` of the payload.
     #[allow(clippy::borrow_interior_mutable_const)]
     pub fn new(r: HttpRequest, payload: &mut Payload) -> Self {
-        let length = r
+        let config = RicksponseConfig::from_req(&r);
+        let limit = O::MAX_PAYLOAD_SIZE.unwrap_or(config.limit);
+        let length = content_length(&r);
+        let formats = O::FORMATS.unwrap_or_else(|| config.allowed_content_types());
+        let content_type = r
             .headers()
-            .get(&CONTENT_LENGTH)
-            .ok_or(Error::NoPayloadSizeDefinitionInHeader)
-            .and_then(|l| l.to_str().map_err(Error::from))
-            .and_then(|s| s.parse::<usize>().map_err(Error::from));
-        let content_type = Ok(r
-            .headers()
-            .get_all("Content-Type")
-            .filter_map(|h| simple_serde::ContentType::try_from(h).ok())
-            .collect::<Vec<ContentType>>())
-        .and_then(|mut t: Vec<ContentType>| {
-            t.reverse();
-            t.pop().ok_or(Error::FailedToGetContentTypeFromHeader)
-        });
+            .get_all(CONTENT_TYPE)
+            .filter_map(|h| h.to_str().ok())
+            .filter_map(resolve)
+            .find(|(c, _)| formats.contains(c))
+            .ok_or_else(|| PayloadError::ContentType {
+                accepted: accepted_media_types(formats),
+            });
+
+        let decoder = ContentDecoder::from_headers(r.headers(), config);
 
         let payload = payload.take();
 
-        match (content_type, length) {
-            (Ok(c), Ok(l)) => PayloadBody::Body {
-                limit: O::MAX_PAYLOAD_SIZE.unwrap_or(DEFAULT_LIMIT),
-                content_type: c,
-                length: Some(l),
-                payload,
-                buf: BytesMut::with_capacity(O::BUFFER_CAPACITY.unwrap_or(8192)),
-                _res: PhantomData,
-                _payload_res: PhantomData,
-            },
-            (Ok(c), _) => PayloadBody::Body {
-                limit: O::MAX_PAYLOAD_SIZE.unwrap_or(DEFAULT_LIMIT),
+        match (content_type, decoder, length) {
+            (Err(e), _, _) => PayloadBody::Error(Some(e)),
+            (Ok(_), Err(e), _) | (Ok(_), Ok(_), Err(e)) => PayloadBody::Error(Some(e)),
+            (Ok(_), Ok(_), Ok(Some(length))) if length > limit => {
+                PayloadBody::Error(Some(PayloadError::OverflowKnownLength { length, limit }))
+            }
+            (Ok((c, m)), Ok(decoder), Ok(length)) => PayloadBody::Body {
+                limit,
                 content_type: c,
-                length: None,
+                media_type: m,
+                length,
                 payload,
-                buf: BytesMut::with_capacity(O::BUFFER_CAPACITY.unwrap_or(8192)),
+                // With a declared length a small body fits without growing the buffer, an
+                // encoded body is decompressed into a buffer of the decoder instead.
+                buf: match (&decoder, length) {
+                    (Some(_), _) => BytesMut::new(),
+                    (None, Some(length)) => BytesMut::with_capacity(length.min(MAX_PREALLOCATION)),
+                    (None, None) => BytesMut::with_capacity(
+                        O::BUFFER_CAPACITY.unwrap_or(config.buffer_capacity),
+                    ),
+                },
+                received: 0,
+                decoder,
                 _res: PhantomData,
                 _payload_res: PhantomData,
             },
-            (_, _) => PayloadBody::Error(Some(PayloadError::ContentType)),
         }
     }
 
-    /// Set maximum accepted payload size. The default limit is 2MB.
-    pub fn limit(self, limit: usize) -> Self {
+    /// The media type of the request body, `None` when it could not be resolved.
+    pub fn media_type(&self) -> Option<&MediaType> {
         match self {
-            PayloadBody::Body {
-                length,
-                content_type,
-                payload,
-                buf,
-                ..
-            } => {
-                if let Some(len) = length {
-                    if len > limit {
-                        return PayloadBody::Error(Some(PayloadError::OverflowKnownLength {
-                            length: len,
-                            limit,
-                        }));
-                    }
-                }
-
-                PayloadBody::Body {
-                    limit,
-                    content_type,
-                    length,
-                    payload,
-                    buf,
-                    _res: PhantomData,
-                    _payload_res: PhantomData,
-                }
-            }
-            PayloadBody::Error(e) => PayloadBody::Error(e),
+            PayloadBody::Body { media_type, .. } => Some(media_type),
+            PayloadBody::Error(_) => None,
         }
     }
 }
 
+/// The length declared by the `Content-Length` header of a request, `None` when it has none.
+///
+/// A header that is not a valid length is rejected, it is not taken for a missing one.
+pub(crate) fn content_length(r: &HttpRequest) -> Result<Option<usize>, PayloadError> {
+    r.headers()
+        .get(&CONTENT_LENGTH)
+        .map(|l| {
+            l.to_str()
+                .map_err(Error::from)
+                .and_then(|s| s.parse::<usize>().map_err(Error::from))
+        })
+        .transpose()
+        .map_err(|e| PayloadError::ContentLength(Box::new(e)))
+}
+
 impl<T: DeserializeOwned, O: PayloadControl> Future for PayloadBody<T, O> {
     type Output = Result<T, PayloadError>;
 
@@ -120,7 +129,10 @@ impl<T: DeserializeOwned, O: PayloadControl> Future for PayloadBody<T, O> {
         match this {
             PayloadBody::Body {
                 limit,
+                length,
                 buf,
+                received,
+                decoder,
                 payload,
                 content_type,
                 ..
@@ -134,21 +146,26 @@ impl<T: DeserializeOwned, O: PayloadControl> Future for PayloadBody<T, O> {
                 match res {
                     Some(chunk) => {
                         let chunk = chunk?;
-                        let buf_len = buf.len() + chunk.len();
-                        if buf_len > *limit {
+                        *received += chunk.len();
+                        if let Some(length) = length.filter(|length| *received > *length) {
+                            return Poll::Ready(Err(PayloadError::LengthMismatch { length }));
+                        } else if *received > *limit {
                             return Poll::Ready(Err(PayloadError::Overflow { limit: *limit }));
+                        } else if let Some(decoder) = decoder {
+                            decoder.feed(&chunk)?;
                         } else {
                             buf.extend_from_slice(&chunk);
                         }
                     }
                     None => {
-                        let json = buf
-                            .to_vec()
-                            .as_slice()
-                            .decode(content_type.deref())
-                            .map(|d: Decoded<T>| d.into())
-                            .map_err(PayloadError::Deserialize)?;
-                        return Poll::Ready(Ok(json));
+                        // Decoded in place from the buffer the chunks were read into. The serde
+                        // deserializers of `simple_serde` pull from a complete input, none of
+                        // them can be fed chunk by chunk, so decoding starts with the last chunk.
+                        let body = match decoder.take() {
+                            Some(decoder) => decoder.finish()?.freeze(),
+                            None => buf.split().freeze(),
+                        };
+                        return Poll::Ready(decode(&body, content_type));
                     }
                 }
             },
@@ -156,3 +173,117 @@ impl<T: DeserializeOwned, O: PayloadControl> Future for PayloadBody<T, O> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{DefaultControl, Error, PayloadError, RicksponseConfig};
+    use actix_web::{http::StatusCode, test, web, ResponseError};
+    use std::error::Error as _;
+
+    use super::PayloadBody;
+
+    #[derive(Deserialize)]
+    struct Note {
+        text: String,
+    }
+
+    #[actix_web::test]
+    async fn test_buffer_sized_from_content_length() {
+        let (req, mut payload) = test::TestRequest::post()
+            .insert_header(("content-type", "application/json"))
+            .insert_header(("content-length", "16"))
+            .set_payload(r#"{"text":"sized"}"#)
+            .to_http_parts();
+
+        match PayloadBody::<Note, DefaultControl>::new(req, &mut payload) {
+            PayloadBody::Body { buf, .. } => assert_eq!(buf.capacity(), 16),
+            PayloadBody::Error(e) => panic!("{:?}", e),
+        }
+
+        let (req, mut payload) = test::TestRequest::post()
+            .insert_header(("content-type", "application/json"))
+            .insert_header(("content-length", "10485760"))
+            .to_http_parts();
+
+        match PayloadBody::<Note, DefaultControl>::new(req, &mut payload) {
+            PayloadBody::Body { buf, .. } => assert_eq!(buf.capacity(), super::MAX_PREALLOCATION),
+            PayloadBody::Error(e) => panic!("{:?}", e),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_known_length_over_limit() {
+        let (req, mut payload) = test::TestRequest::post()
+            .insert_header(("content-type", "application/json"))
+            .app_data(web::Data::new(RicksponseConfig::default().limit(8)))
+            .insert_header(("content-length", "19"))
+            .set_payload(r#"{"text":"too long"}"#)
+            .to_http_parts();
+
+        let note: Result<Note, PayloadError> =
+            PayloadBody::<Note, DefaultControl>::new(req, &mut payload).await;
+        assert!(matches!(
+            note,
+            Err(PayloadError::OverflowKnownLength {
+                length: 19,
+                limit: 8
+            })
+        ));
+    }
+
+    #[actix_web::test]
+    async fn test_longer_than_content_length() {
+        let (req, mut payload) = test::TestRequest::post()
+            .insert_header(("content-type", "application/json"))
+            .set_payload(r#"{"text":"too long"}"#)
+            .insert_header(("content-length", "4"))
+            .to_http_parts();
+
+        let note: Result<Note, PayloadError> =
+            PayloadBody::<Note, DefaultControl>::new(req, &mut payload).await;
+        assert!(matches!(
+            note,
+            Err(PayloadError::LengthMismatch { length: 4 })
+        ));
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_content_length() {
+        let (req, mut payload) = test::TestRequest::post()
+            .insert_header(("content-type", "application/json"))
+            .set_payload(r#"{"text":"invalid"}"#)
+            .insert_header(("content-length", "eighteen"))
+            .to_http_parts();
+
+        let note: Result<Note, PayloadError> =
+            PayloadBody::<Note, DefaultControl>::new(req, &mut payload).await;
+        match note {
+            Err(e @ PayloadError::ContentLength(_)) => {
+                assert_eq!(e.status_code(), StatusCode::BAD_REQUEST);
+                assert!(matches!(
+                    e.source().and_then(|e| e.downcast_ref::<Error>()),
+                    Some(Error::FailedToParseToInt(_))
+                ));
+            }
+            note => panic!("{:?}", note.map(|n| n.text)),
+        }
+    }
+
+    #[cfg(feature = "compress-gzip")]
+    #[actix_web::test]
+    async fn test_decompressed_body() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(br#"{"text":"compressed"}"#).unwrap();
+        let (req, mut payload) = test::TestRequest::post()
+            .insert_header(("content-type", "application/json"))
+            .insert_header(("content-encoding", "gzip"))
+            .set_payload(encoder.finish().unwrap())
+            .to_http_parts();
+
+        let note: Result<Note, PayloadError> =
+            PayloadBody::<Note, DefaultControl>::new(req, &mut payload).await;
+        assert_eq!(note.unwrap().text, "compressed");
+    }
+}
@@ -1,4 +1,105 @@
+use simple_serde::ContentType;
+
+/// Per payload overrides of the `RicksponseConfig` limits, `None` keeps the configured value.
+///
+/// The control is picked through the second type parameter of the extractor, `Ricksponse<T, C>`
+/// and `Hateoas<T, C>`, so it can be the payload type itself or a marker type for a route.
+/// Wrapped in `Validated`, eg. `Ricksponse<T, Validated<C>>`, the payload is validated as well.
+///
+/// ```rust
+/// use ricksponse::{PayloadControl, Ricksponse};
+///
+/// struct LargeUpload;
+///
+/// impl PayloadControl for LargeUpload {
+///     const MAX_PAYLOAD_SIZE: Option<usize> = Some(104_857_600);
+/// }
+///
+/// async fn upload(file: Ricksponse<Vec<u8>, LargeUpload>) -> String {
+///     format!("{:?}", file)
+/// }
+/// ```
+///
+/// With the `derive` feature the control can be derived, sizes are written human-readable and
+/// checked at compile time:
+///
+#[cfg_attr(feature = "derive", doc = "```rust")]
+#[cfg_attr(not(feature = "derive"), doc = "```rust,ignore")]
+/// use ricksponse::PayloadControl;
+///
+/// #[derive(PayloadControl)]
+/// #[payload(max_size = "10MiB", buffer = "64KiB", formats(json, cbor))]
+/// struct Upload;
+///
+/// assert_eq!(Upload::MAX_PAYLOAD_SIZE, Some(10_485_760));
+/// ```
 pub trait PayloadControl {
-    const MAX_PAYLOAD_SIZE: Option<usize>;
-    const BUFFER_CAPACITY: Option<usize>;
+    const MAX_PAYLOAD_SIZE: Option<usize> = None;
+    const BUFFER_CAPACITY: Option<usize> = None;
+    /// The request body formats accepted, overriding the content types of the `RicksponseConfig`.
+    /// A body in another format is rejected with `415 Unsupported Media Type`.
+    const FORMATS: Option<&'static [ContentType]> = None;
+}
+
+/// The control used when none is given, it keeps every configured value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DefaultControl;
+
+impl PayloadControl for DefaultControl {}
+
+#[cfg(all(test, feature = "derive"))]
+mod test {
+    use crate::{ContentType, PayloadControl, Ricksponse};
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::{http::header, web, App};
+
+    #[derive(PayloadControl)]
+    #[payload(max_size = "10MiB", buffer = "64KiB", formats(json, cbor))]
+    struct Upload;
+
+    #[derive(PayloadControl)]
+    #[payload(max_size = 2048)]
+    struct Small;
+
+    #[derive(PayloadControl)]
+    struct Unbounded;
+
+    #[test]
+    fn test_derive_payload_control() {
+        assert_eq!(Upload::MAX_PAYLOAD_SIZE, Some(10 * 1024 * 1024));
+        assert_eq!(Upload::BUFFER_CAPACITY, Some(64 * 1024));
+        assert_eq!(
+            Upload::FORMATS,
+            Some(&[ContentType::Json, ContentType::Cbor][..])
+        );
+
+        assert_eq!(Small::MAX_PAYLOAD_SIZE, Some(2048));
+        assert_eq!(Small::BUFFER_CAPACITY, None);
+        assert_eq!(Small::FORMATS, None);
+
+        assert_eq!(Unbounded::MAX_PAYLOAD_SIZE, None);
+    }
+
+    #[actix_web::test]
+    async fn test_derived_formats_restrict_request_body() {
+        let app = init_service(App::new().route(
+            "/",
+            web::post().to(|name: Ricksponse<String, Upload>| async move {
+                matches!(name, Ricksponse::Data { .. }).to_string()
+            }),
+        ))
+        .await;
+
+        for (content_type, payload, extracted) in [
+            ("application/json", r#""rick""#, "true"),
+            ("application/yaml", "rick", "false"),
+        ] {
+            let req = TestRequest::post()
+                .insert_header((header::CONTENT_TYPE, content_type))
+                .set_payload(payload)
+                .to_request();
+            let body = read_body(call_service(&app, req).await).await;
+            assert_eq!(body, extracted);
+        }
+    }
 }
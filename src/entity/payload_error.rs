@@ -1,13 +1,20 @@
-use crate::entity::ricksponse::ricksponse::DebuggableAny;
+use crate::entity::api_error::ApiError;
+use crate::entity::decode::DecodeError;
+use crate::entity::validation::ValidationErrors;
+use crate::error::Error;
 use actix_http::error;
-use actix_web::ResponseError;
+use actix_http::header::{HeaderName, HeaderValue};
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
 use derive_more::Display;
-use http::StatusCode;
+use http::{Method, StatusCode};
+use serde_json::Value;
 
 #[derive(Debug, Display)]
 #[non_exhaustive]
 pub enum PayloadError {
     /// Payload size is bigger than allowed & content length header set. (default: 2MB)
+    ///
+    /// The size of a body with a content encoding is its compressed size.
     #[display(
         fmt = "Ricksponse payload ({} bytes) is larger than allowed (limit: {} bytes).",
         length,
@@ -16,16 +23,52 @@ pub enum PayloadError {
     OverflowKnownLength { length: usize, limit: usize },
 
     /// Payload size is bigger than allowed but no content length header set. (default: 2MB)
+    ///
+    /// The size of a body with a content encoding is its compressed size.
     #[display(fmt = "payload has exceeded limit ({} bytes).", limit)]
     Overflow { limit: usize },
 
-    /// Content type error
-    #[display(fmt = "Content type error")]
-    ContentType,
+    /// Decompressed payload size is bigger than allowed, see `RicksponseConfig::decompressed_limit`.
+    #[display(fmt = "decompressed payload has exceeded limit ({} bytes).", limit)]
+    DecompressedOverflow { limit: usize },
 
-    /// Deserialize error
-    #[display(fmt = "Deserialize error: {:?}", _0)]
-    Deserialize(simple_serde::Error),
+    /// Decompressed payload is too large compared to its compressed size, see
+    /// `RicksponseConfig::compression_ratio_limit`.
+    #[display(
+        fmt = "decompressed payload has exceeded {} times its compressed size.",
+        limit
+    )]
+    CompressionRatio { limit: usize },
+
+    /// Content encoding of the payload is not supported.
+    #[display(fmt = "Unsupported content encoding: {}", _0)]
+    ContentEncoding(String),
+
+    /// Content length header is present but not a valid length.
+    #[display(fmt = "Invalid content length: {}", _0)]
+    ContentLength(Box<Error>),
+
+    /// More bytes were sent than the content length header declared.
+    #[display(
+        fmt = "payload is longer than its declared content length ({} bytes).",
+        length
+    )]
+    LengthMismatch { length: usize },
+
+    /// Content type of the payload is missing or not accepted, with the media types that are.
+    #[display(
+        fmt = "Unsupported content type, accepted: {}.",
+        "accepted.join(\", \")"
+    )]
+    ContentType { accepted: Vec<&'static str> },
+
+    /// Deserialize error, with the location in the payload where decoding failed.
+    #[display(fmt = "Deserialize error: {}", _0)]
+    Deserialize(Box<DecodeError>),
+
+    /// The decoded payload failed validation, see `Validate`.
+    #[display(fmt = "Validation error: {}", _0)]
+    Validation(ValidationErrors),
 
     /// Serialize error
     #[display(fmt = "Serialize error: {:?}", _0)]
@@ -44,7 +87,85 @@ pub enum PayloadError {
     PayloadError(String, Box<PayloadError>),
 }
 
-impl DebuggableAny for PayloadError {}
+impl PayloadError {
+    /// The media types a payload is accepted as, when it was rejected for its content type.
+    pub fn accepted(&self) -> Option<&[&'static str]> {
+        match self {
+            Self::ContentType { accepted } => Some(accepted),
+            Self::PayloadError(_, err) => err.accepted(),
+            _ => None,
+        }
+    }
+}
+
+/// The header advertising the media types a payload rejected for its content type is accepted
+/// as, `Accept-Patch` (RFC 5789) answering a `PATCH` request and `Accept-Post` any other.
+pub(crate) fn accept_header(
+    error: &dyn ApiError,
+    req: &HttpRequest,
+) -> Option<(HeaderName, HeaderValue)> {
+    let accepted = error.downcast_ref::<PayloadError>()?.accepted()?;
+    let name = match *req.method() {
+        Method::PATCH => HeaderName::from_static("accept-patch"),
+        _ => HeaderName::from_static("accept-post"),
+    };
+    HeaderValue::from_str(&accepted.join(", "))
+        .ok()
+        .map(|value| (name, value))
+}
+
+impl ApiError for PayloadError {
+    fn status(&self) -> u16 {
+        self.status_code().as_u16()
+    }
+
+    fn code(&self) -> Option<&str> {
+        match self {
+            Self::OverflowKnownLength { .. } | Self::Overflow { .. } => Some("payload.too_large"),
+            Self::DecompressedOverflow { .. } => Some("payload.decompressed_too_large"),
+            Self::CompressionRatio { .. } => Some("payload.compression_ratio"),
+            Self::ContentEncoding(_) => Some("payload.content_encoding"),
+            Self::ContentLength(err) => err.code(),
+            Self::LengthMismatch { .. } => Some("payload.length_mismatch"),
+            Self::ContentType { .. } => Some("payload.content_type"),
+            Self::Deserialize(_) => Some("payload.deserialize"),
+            Self::Validation(errors) => errors.code(),
+            Self::Serialize(_) => Some("payload.serialize"),
+            Self::Payload(_) => Some("payload.read"),
+            Self::PayloadError(_, err) => err.code(),
+        }
+    }
+
+    /// Client errors explain themselves, server errors are not detailed to the client.
+    fn message(&self) -> Option<String> {
+        if self.status_code().is_client_error() {
+            Some(self.to_string())
+        } else {
+            None
+        }
+    }
+
+    fn details(&self) -> Option<Value> {
+        match self {
+            Self::ContentType { accepted } => serde_json::to_value(accepted).ok(),
+            Self::Deserialize(e) => serde_json::to_value(&e.location).ok(),
+            Self::Validation(errors) => errors.details(),
+            Self::PayloadError(_, err) => err.details(),
+            _ => None,
+        }
+    }
+}
+
+impl std::error::Error for PayloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::ContentLength(err) => Some(err.as_ref()),
+            Self::Payload(err) => Some(err),
+            Self::PayloadError(_, err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 impl From<error::PayloadError> for PayloadError {
     fn from(err: error::PayloadError) -> Self {
@@ -60,10 +181,43 @@ impl ResponseError for PayloadError {
                 limit: _,
             } => StatusCode::PAYLOAD_TOO_LARGE,
             Self::Overflow { limit: _ } => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::DecompressedOverflow { .. } | Self::CompressionRatio { .. } => {
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
+            Self::ContentType { .. } | Self::ContentEncoding(_) => {
+                StatusCode::UNSUPPORTED_MEDIA_TYPE
+            }
+            Self::ContentLength(err) => err.status_code(),
+            Self::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
             Self::Serialize(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::Payload(err) => err.status_code(),
             Self::PayloadError(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
             _ => StatusCode::BAD_REQUEST,
         }
     }
+
+    /// Problem details encoded as `application/problem+json`, see `negotiated_error` to encode
+    /// them in the format negotiated with the client.
+    fn error_response(&self) -> HttpResponse {
+        self.problem().json_response()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ApiError, PayloadError};
+
+    #[test]
+    fn test_server_errors_are_not_detailed() {
+        let error = PayloadError::Serialize(simple_serde::Error::Infallible);
+        assert_eq!(error.status(), 500);
+        assert_eq!(error.message(), None);
+        assert_eq!(error.problem().detail, None);
+
+        let error = PayloadError::Overflow { limit: 4 };
+        assert_eq!(
+            error.message().unwrap(),
+            "payload has exceeded limit (4 bytes)."
+        );
+    }
 }
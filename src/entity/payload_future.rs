@@ -1,7 +1,10 @@
+use crate::entity::api_error::negotiated_error;
+use crate::entity::negotiation::MediaType;
 use crate::entity::payload_body::PayloadBody;
 use crate::entity::payload_control::PayloadControl;
 use crate::entity::payload_error::PayloadError;
-use crate::error::Error;
+use crate::entity::ricksponse_config::RicksponseConfig;
+use crate::entity::validation::{Validate, ValidationErrors};
 use actix_web::HttpRequest;
 use serde::de::DeserializeOwned;
 use std::future::Future;
@@ -9,11 +12,22 @@ use std::marker::PhantomData;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
+/// Builds the extracted value from the decoded payload and the media type it was sent as.
+///
+/// An extractor returning the error rejects the request with it.
+pub trait FromPayload<T>: Sized {
+    fn from_payload(
+        res: Result<T, PayloadError>,
+        media_type: Option<MediaType>,
+    ) -> Result<Self, PayloadError>;
+}
+
 pub struct PayloadFuture<O, T, U> {
-    pub(crate) _req: Option<HttpRequest>,
+    pub(crate) req: Option<HttpRequest>,
     pub(crate) fut: PayloadBody<T, O>,
     pub(crate) phantom: PhantomData<U>,
     pub(crate) phantom_triat: PhantomData<O>,
+    pub(crate) validate: fn(&T) -> Result<(), ValidationErrors>,
 }
 
 impl<O, T, U> Unpin for PayloadFuture<O, T, U> {}
@@ -21,22 +35,28 @@ impl<O, T, U> Unpin for PayloadFuture<O, T, U> {}
 impl<T: DeserializeOwned, U, O: PayloadControl> PayloadFuture<O, T, U> {
     pub(crate) fn new(r: HttpRequest, p: &mut actix_http::Payload) -> PayloadFuture<O, T, U> {
         PayloadFuture {
-            _req: Some(r.clone()),
+            req: Some(r.clone()),
             fut: PayloadBody::new(r, p),
-            phantom: PhantomData::default(),
-            phantom_triat: PhantomData::default(),
+            phantom: PhantomData,
+            phantom_triat: PhantomData,
+            validate: |_| Ok(()),
         }
     }
-}
 
-pub struct Payload<T> {
-    data: T,
+    /// A future that validates the decoded payload, see `Validate`.
+    pub(crate) fn validated(r: HttpRequest, p: &mut actix_http::Payload) -> PayloadFuture<O, T, U>
+    where
+        T: Validate,
+    {
+        PayloadFuture {
+            validate: T::validate,
+            ..Self::new(r, p)
+        }
+    }
 }
 
-impl<T: DeserializeOwned, U: From<Result<T, PayloadError>>, O: PayloadControl> Future
-    for PayloadFuture<O, T, U>
-{
-    type Output = Result<U, Error>;
+impl<T: DeserializeOwned, U: FromPayload<T>, O: PayloadControl> Future for PayloadFuture<O, T, U> {
+    type Output = Result<U, actix_web::Error>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.get_mut();
@@ -46,9 +66,31 @@ impl<T: DeserializeOwned, U: From<Result<T, PayloadError>>, O: PayloadControl> F
                 return std::task::Poll::Pending;
             }
         };
-        Poll::Ready(match res {
-            Err(err) => Ok(U::from(Err(err.into()) as Result<T, PayloadError>)),
-            Ok(data) => Ok(U::from(Ok(data) as Result<T, PayloadError>)),
+        let media_type = this.fut.media_type().cloned();
+        let res = res.and_then(|data| match (this.validate)(&data) {
+            Ok(()) => Ok(data),
+            Err(errors) => Err(PayloadError::Validation(errors)),
+        });
+        let req = this.req.take();
+        let res = match (res, &req) {
+            // A payload failing validation never reaches the handler.
+            (Err(err @ PayloadError::Validation(_)), Some(req)) => {
+                let rejection = match RicksponseConfig::from_req(req).handle_error(err, req) {
+                    Ok(rejection) => rejection,
+                    Err(err) => negotiated_error(err, req),
+                };
+                return Poll::Ready(Err(rejection));
+            }
+            (Err(err), Some(req)) => match RicksponseConfig::from_req(req).handle_error(err, req) {
+                Ok(rejection) => return Poll::Ready(Err(rejection)),
+                Err(err) => Err(err),
+            },
+            (res, _) => res,
+        };
+        Poll::Ready(match (U::from_payload(res, media_type), req) {
+            (Ok(extracted), _) => Ok(extracted),
+            (Err(err), Some(req)) => Err(negotiated_error(err, &req)),
+            (Err(err), None) => Err(err.into()),
         })
     }
 }
@@ -0,0 +1,419 @@
+use crate::entity::decode::{decode, DecodeError};
+use crate::entity::negotiation::MediaType;
+use crate::entity::payload_body::content_length;
+use crate::entity::payload_error::PayloadError;
+use crate::entity::ricksponse_config::RicksponseConfig;
+use actix_http::Payload;
+use actix_web::HttpRequest;
+use bytes::{Buf, BytesMut};
+use futures_core::Stream;
+use http::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use simple_serde::ContentType;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+
+/// ASCII record separator, the start of every record of a JSON text sequence.
+const RECORD_SEPARATOR: u8 = 0x1e;
+
+/// How the records of a streamed request body are delimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Framing {
+    /// NDJSON, one JSON text per line.
+    Lines,
+    /// JSON text sequences, RFC 7464.
+    RecordSeparator,
+    /// CBOR sequences, RFC 8742, items follow each other without delimiter.
+    Cbor,
+}
+
+impl Framing {
+    fn resolve(media_type: &MediaType) -> Option<Framing> {
+        match media_type.essence().as_str() {
+            "application/x-ndjson" | "application/ndjson" | "application/jsonl" => {
+                Some(Framing::Lines)
+            }
+            "application/json-seq" => Some(Framing::RecordSeparator),
+            "application/cbor-seq" => Some(Framing::Cbor),
+            _ => None,
+        }
+    }
+
+    /// The media types of the framings whose records are decoded with an allowed content type.
+    fn accepted(allowed: &[ContentType]) -> Vec<&'static str> {
+        [
+            (Framing::Lines, "application/x-ndjson"),
+            (Framing::RecordSeparator, "application/json-seq"),
+            (Framing::Cbor, "application/cbor-seq"),
+        ]
+        .into_iter()
+        .filter(|(framing, _)| allowed.contains(framing.content_type()))
+        .map(|(_, media_type)| media_type)
+        .collect()
+    }
+
+    /// The format a single record is decoded with.
+    fn content_type(self) -> &'static ContentType {
+        match self {
+            Framing::Lines | Framing::RecordSeparator => &ContentType::Json,
+            Framing::Cbor => &ContentType::Cbor,
+        }
+    }
+}
+
+/// How far a `CborScanner` got into a CBOR data item.
+#[derive(Debug, PartialEq, Eq)]
+enum Scanned {
+    /// The item ends at the offset.
+    Complete(usize),
+    /// More of the body is needed.
+    Incomplete,
+    /// The bytes are no well-formed CBOR.
+    Malformed,
+}
+
+/// Finds the end of the CBOR data item at the start of the buffer while the buffer fills.
+///
+/// Only the heads of the items are read, and every head once, so a record dripping in chunk by
+/// chunk is not parsed again for every chunk. The item is decoded once it is complete.
+#[derive(Debug, Default)]
+struct CborScanner {
+    /// Offset of the next head to read.
+    pos: usize,
+    /// Items left in every enclosing array, map or tag, `None` for an indefinite length.
+    open: Vec<Option<u64>>,
+}
+
+impl CborScanner {
+    fn scan(&mut self, buf: &[u8]) -> Scanned {
+        while let Some(&initial) = buf.get(self.pos) {
+            let (major, info) = (initial >> 5, initial & 0x1f);
+            let (argument, head) = match info {
+                0..=23 => (u64::from(info), 1),
+                24..=27 => {
+                    let size = 1 << (info - 24);
+                    match buf.get(self.pos + 1..self.pos + 1 + size) {
+                        Some(bytes) => (
+                            bytes.iter().fold(0, |n, b| n << 8 | u64::from(*b)),
+                            1 + size,
+                        ),
+                        None => return Scanned::Incomplete,
+                    }
+                }
+                31 => (0, 1),
+                _ => return Scanned::Malformed,
+            };
+            let end = self.pos + head;
+            let complete = match (major, info == 31) {
+                // Break, the end of an indefinite length item.
+                (7, true) => match self.open.pop() {
+                    Some(None) => true,
+                    _ => return Scanned::Malformed,
+                },
+                (0 | 1 | 6, true) => return Scanned::Malformed,
+                (0 | 1 | 7, false) => true,
+                (2 | 3, false) => {
+                    let end = usize::try_from(argument)
+                        .ok()
+                        .and_then(|length| end.checked_add(length));
+                    match end {
+                        Some(end) if end <= buf.len() => {
+                            self.pos = end;
+                            self.close()
+                        }
+                        Some(_) => return Scanned::Incomplete,
+                        None => return Scanned::Malformed,
+                    };
+                    if self.open.is_empty() {
+                        return Scanned::Complete(self.pos);
+                    }
+                    continue;
+                }
+                (4, false) | (5, false) | (6, false) => {
+                    let items = match major {
+                        5 => argument.checked_mul(2),
+                        6 => Some(1),
+                        _ => Some(argument),
+                    };
+                    match items {
+                        Some(0) => true,
+                        Some(items) => {
+                            self.open.push(Some(items));
+                            false
+                        }
+                        None => return Scanned::Malformed,
+                    }
+                }
+                _ => {
+                    self.open.push(None);
+                    false
+                }
+            };
+            self.pos = end;
+            if complete {
+                self.close();
+            }
+            if self.open.is_empty() {
+                return Scanned::Complete(self.pos);
+            }
+        }
+        Scanned::Incomplete
+    }
+
+    /// Counts a completed item against the enclosing items, closing those it completes.
+    fn close(&mut self) {
+        while let Some(Some(left)) = self.open.last_mut() {
+            *left -= 1;
+            if *left > 0 {
+                return;
+            }
+            self.open.pop();
+        }
+    }
+}
+
+/// Decodes the records of a request body one by one while the body arrives.
+///
+/// Every record is limited by `RicksponseConfig::limit`, the body as a whole by
+/// `RicksponseConfig::stream_limit`. A record that fails to decode is reported and skipped when
+/// the framing allows to find the next one, any other error ends the stream. Bodies with a
+/// `Content-Encoding` are rejected with `PayloadError::ContentEncoding`.
+pub struct PayloadStream<T> {
+    payload: Payload,
+    framing: Framing,
+    media_type: MediaType,
+    buf: BytesMut,
+    /// Bytes at the start of the buffer already searched for the end of the record.
+    scanned: usize,
+    cbor: CborScanner,
+    item_limit: usize,
+    limit: usize,
+    read: usize,
+    eof: bool,
+    done: bool,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T> PayloadStream<T> {
+    /// Create a stream decoding the request payload, or the error that keeps it from being read.
+    pub fn new(req: &HttpRequest, payload: &mut Payload) -> Result<Self, PayloadError> {
+        let config = RicksponseConfig::from_req(req);
+        let (framing, media_type) = req
+            .headers()
+            .get_all(CONTENT_TYPE)
+            .filter_map(|h| h.to_str().ok())
+            .filter_map(|h| MediaType::from_str(h).ok())
+            .find_map(|m| Framing::resolve(&m).map(|framing| (framing, m)))
+            .filter(|(framing, _)| {
+                config
+                    .allowed_content_types()
+                    .contains(framing.content_type())
+            })
+            .ok_or_else(|| PayloadError::ContentType {
+                accepted: Framing::accepted(config.allowed_content_types()),
+            })?;
+        // Records are cut from the body as it arrives, decompressing them would bypass the
+        // limits of `RicksponseConfig::decompressed_limit`.
+        let encoding = req
+            .headers()
+            .get(CONTENT_ENCODING)
+            .map(|e| String::from_utf8_lossy(e.as_bytes()).trim().to_string());
+        if let Some(encoding) = encoding.filter(|e| !e.eq_ignore_ascii_case("identity")) {
+            return Err(PayloadError::ContentEncoding(encoding));
+        }
+        let length = content_length(req)?;
+        if let Some(length) = length.filter(|length| *length > config.stream_limit) {
+            return Err(PayloadError::OverflowKnownLength {
+                length,
+                limit: config.stream_limit,
+            });
+        }
+        Ok(PayloadStream {
+            payload: payload.take(),
+            framing,
+            media_type,
+            buf: BytesMut::with_capacity(config.buffer_capacity),
+            scanned: 0,
+            cbor: CborScanner::default(),
+            item_limit: config.limit,
+            limit: config.stream_limit,
+            read: 0,
+            eof: false,
+            done: false,
+            _item: PhantomData,
+        })
+    }
+
+    /// The media type of the request body, eg. `application/x-ndjson`.
+    pub fn media_type(&self) -> &MediaType {
+        &self.media_type
+    }
+
+    /// Cuts the next complete record off the buffer, `Ok(None)` when more of the body is needed.
+    fn next_record(&mut self) -> Result<Option<BytesMut>, PayloadError> {
+        loop {
+            let record = match self.framing {
+                Framing::Lines => match self.find(0, b'\n') {
+                    Some(end) => {
+                        let record = self.buf.split_to(end);
+                        self.buf.advance(1);
+                        Some(record)
+                    }
+                    None if self.eof => Some(self.buf.split()),
+                    None => None,
+                },
+                Framing::RecordSeparator => {
+                    // Records are read up to the start of the next one, a pretty printed JSON
+                    // text may span several lines.
+                    match self.find(1, RECORD_SEPARATOR) {
+                        Some(end) => Some(self.buf.split_to(end)),
+                        None if self.eof => Some(self.buf.split()),
+                        None => None,
+                    }
+                    .map(|mut record| {
+                        if record.first() == Some(&RECORD_SEPARATOR) {
+                            record.advance(1);
+                        }
+                        record
+                    })
+                }
+                Framing::Cbor => {
+                    if self.buf.is_empty() {
+                        return Ok(None);
+                    }
+                    match self.cbor.scan(&self.buf) {
+                        Scanned::Complete(end) => {
+                            self.cbor = CborScanner::default();
+                            Some(self.buf.split_to(end))
+                        }
+                        Scanned::Incomplete if !self.eof => None,
+                        _ => {
+                            // The framing is lost, report why the item does not decode.
+                            let e = serde_cbor::from_slice::<IgnoredAny>(&self.buf)
+                                .err()
+                                .unwrap_or_else(|| {
+                                    serde::de::Error::custom("malformed CBOR data item")
+                                });
+                            let e = DecodeError::from(simple_serde::Error::from(e));
+                            return Err(PayloadError::Deserialize(Box::new(e)));
+                        }
+                    }
+                }
+            };
+            match record {
+                // Blank lines and empty records carry no item.
+                Some(record) if record.iter().all(u8::is_ascii_whitespace) => {
+                    if self.buf.is_empty() && self.eof {
+                        return Ok(None);
+                    }
+                }
+                record => return Ok(record),
+            }
+        }
+    }
+}
+
+impl<T> PayloadStream<T> {
+    /// The offset of the first `delimiter` at or after `from`, resuming where the last search of
+    /// the buffer stopped.
+    fn find(&mut self, from: usize, delimiter: u8) -> Option<usize> {
+        let start = self.scanned.max(from).min(self.buf.len());
+        match self.buf[start..].iter().position(|b| *b == delimiter) {
+            Some(offset) => {
+                self.scanned = 0;
+                Some(start + offset)
+            }
+            None => {
+                self.scanned = self.buf.len();
+                None
+            }
+        }
+    }
+}
+
+impl<T> Unpin for PayloadStream<T> {}
+
+impl<T: DeserializeOwned> Stream for PayloadStream<T> {
+    type Item = Result<T, PayloadError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+            match this.next_record() {
+                Ok(Some(record)) if record.len() > this.item_limit => {
+                    return Poll::Ready(Some(Err(PayloadError::OverflowKnownLength {
+                        length: record.len(),
+                        limit: this.item_limit,
+                    })));
+                }
+                Ok(Some(record)) => {
+                    return Poll::Ready(Some(decode(&record, this.framing.content_type())));
+                }
+                Ok(None) if this.eof => {
+                    this.done = true;
+                    return Poll::Ready(None);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+            }
+            if this.buf.len() > this.item_limit {
+                this.done = true;
+                return Poll::Ready(Some(Err(PayloadError::Overflow {
+                    limit: this.item_limit,
+                })));
+            }
+            match Pin::new(&mut this.payload).poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => {
+                    this.read += chunk.len();
+                    if this.read > this.limit {
+                        this.done = true;
+                        return Poll::Ready(Some(Err(PayloadError::Overflow {
+                            limit: this.limit,
+                        })));
+                    }
+                    this.buf.extend_from_slice(&chunk);
+                }
+                Poll::Ready(Some(Err(e))) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(e.into())));
+                }
+                Poll::Ready(None) => this.eof = true,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CborScanner, Scanned};
+
+    #[test]
+    fn test_cbor_scanner() {
+        // {"a": [_ h'01', 1(2)]} followed by the next item.
+        let item = b"\xa1\x61a\x9f\x41\x01\xc1\x02\xff\x00";
+        let mut scanner = CborScanner::default();
+        for end in 0..item.len() - 2 {
+            assert_eq!(scanner.scan(&item[..end]), Scanned::Incomplete, "{}", end);
+        }
+        assert_eq!(scanner.scan(item), Scanned::Complete(9));
+
+        for malformed in [&b"\xff"[..], b"\x1c", b"\x81\xff", b"\xdf"] {
+            let mut scanner = CborScanner::default();
+            assert_eq!(
+                scanner.scan(malformed),
+                Scanned::Malformed,
+                "{:?}",
+                malformed
+            );
+        }
+    }
+}
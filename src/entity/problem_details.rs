@@ -0,0 +1,281 @@
+use crate::entity::negotiation::{
+    append_vary, negotiate, structured_syntax_suffix, MediaType, NEGOTIATION_HEADERS,
+};
+use crate::entity::ricksponse_config::RicksponseConfig;
+use actix_http::body::BoxBody;
+use actix_http::header::HeaderName;
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder, Responder};
+use http::StatusCode;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{Map, Value};
+use simple_serde::{ContentType, SimpleEncoder};
+use std::mem;
+use std::str::FromStr;
+
+/// The problem type of a problem that carries no semantics beyond its status code.
+pub const ABOUT_BLANK: &str = "about:blank";
+
+const PROBLEM_JSON: &str = "application/problem+json";
+
+/// An RFC 9457 (formerly RFC 7807) problem details object.
+///
+/// When responding, a problem without an `instance` is given the path of the request. Problems are
+/// encoded in the format negotiated from the `Accept` header of the request. Formats
+/// with a structured syntax suffix are announced as `application/problem+json`,
+/// `application/problem+yaml` and `application/problem+cbor`, any other format is announced with
+/// its own media type. A client accepting none of the allowed formats still gets
+/// `application/problem+json`, an error should not be swallowed by a `406 Not Acceptable`.
+///
+/// ## Example
+///
+/// ```rust
+/// use ricksponse::ProblemDetails;
+///
+/// let problem = ProblemDetails::new(403)
+///     .problem_type("https://example.com/probs/out-of-credit")
+///     .title("You do not have enough credit.")
+///     .detail("Your current balance is 30, but that costs 50.")
+///     .instance("/account/12345/msgs/abc")
+///     .extension("balance", 30);
+///
+/// assert_eq!(problem.extensions["balance"], 30);
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ProblemDetails {
+    /// A URI reference identifying the problem type, `about:blank` by default.
+    #[serde(rename = "type", default = "about_blank")]
+    pub problem_type: String,
+    /// A short, human-readable summary of the problem type.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The HTTP status code of this occurrence of the problem.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// A human-readable explanation specific to this occurrence of the problem.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// A URI reference identifying this occurrence of the problem.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Extension members, serialized next to the standard members. Members named like one of the
+    /// standard members are left out when serializing.
+    #[serde(flatten)]
+    pub extensions: Map<String, Value>,
+}
+
+fn about_blank() -> String {
+    ABOUT_BLANK.to_string()
+}
+
+/// The names of the standard members, they win over extension members of the same name.
+const STANDARD_MEMBERS: [&str; 5] = ["type", "title", "status", "detail", "instance"];
+
+impl Serialize for ProblemDetails {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("type", &self.problem_type)?;
+        if let Some(title) = &self.title {
+            map.serialize_entry("title", title)?;
+        }
+        if let Some(status) = &self.status {
+            map.serialize_entry("status", status)?;
+        }
+        if let Some(detail) = &self.detail {
+            map.serialize_entry("detail", detail)?;
+        }
+        if let Some(instance) = &self.instance {
+            map.serialize_entry("instance", instance)?;
+        }
+        for (key, value) in &self.extensions {
+            if !STANDARD_MEMBERS.contains(&key.as_str()) {
+                map.serialize_entry(key, value)?;
+            }
+        }
+        map.end()
+    }
+}
+
+impl ProblemDetails {
+    /// A blank problem for the given status code, titled with the reason phrase of the status.
+    pub fn new(status: u16) -> Self {
+        Self {
+            problem_type: about_blank(),
+            title: StatusCode::from_u16(status)
+                .ok()
+                .and_then(|s| s.canonical_reason())
+                .map(str::to_string),
+            status: Some(status),
+            detail: None,
+            instance: None,
+            extensions: Map::new(),
+        }
+    }
+
+    pub fn problem_type<S: Into<String>>(mut self, problem_type: S) -> Self {
+        self.problem_type = problem_type.into();
+        self
+    }
+
+    pub fn title<S: Into<String>>(mut self, title: S) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn detail<S: Into<String>>(mut self, detail: S) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn instance<S: Into<String>>(mut self, instance: S) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Add an extension member. Members named like one of the standard members are left out when
+    /// serializing.
+    pub fn extension<K: Into<String>, V: Into<Value>>(mut self, key: K, value: V) -> Self {
+        self.extensions.insert(key.into(), value.into());
+        self
+    }
+
+    /// The status code to respond with, `500 Internal Server Error` when no valid one is set.
+    pub fn status_code(&self) -> StatusCode {
+        self.status
+            .and_then(|s| StatusCode::from_u16(s).ok())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+/// The media type a problem encoded as `content_type` is announced with.
+pub(crate) fn problem_media_type(content_type: &ContentType, media_type: &MediaType) -> MediaType {
+    structured_syntax_suffix(content_type)
+        .and_then(|suffix| MediaType::from_str(&format!("application/problem+{}", suffix)).ok())
+        .unwrap_or_else(|| media_type.clone())
+}
+
+impl ProblemDetails {
+    /// Encode the problem as `application/problem+json`, for when there is no request to negotiate
+    /// a format with.
+    pub(crate) fn json_response(self) -> HttpResponse {
+        let media_type = MediaType::from_str(PROBLEM_JSON).expect("valid media type");
+        self.encoded_response(&ContentType::Json, media_type)
+    }
+
+    fn encoded_response(self, content_type: &ContentType, media_type: MediaType) -> HttpResponse {
+        match self.encode(content_type) {
+            Ok(mut body) => HttpResponseBuilder::new(self.status_code())
+                .content_type(media_type.to_string())
+                .body(mem::take(&mut *body)),
+            Err(_) => HttpResponse::new(self.status_code()),
+        }
+    }
+}
+
+impl Responder for ProblemDetails {
+    type Body = BoxBody;
+
+    fn respond_to(mut self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+        if self.instance.is_none() {
+            self.instance = Some(req.path().to_string());
+        }
+        let config = RicksponseConfig::from_req(req);
+        let mut res = match negotiate(req.headers(), config.allowed_content_types()) {
+            Ok(negotiated) => self.encoded_response(
+                negotiated.content_type(),
+                problem_media_type(negotiated.content_type(), negotiated.media_type()),
+            ),
+            Err(_) => self.json_response(),
+        };
+        append_vary(
+            res.headers_mut(),
+            NEGOTIATION_HEADERS.iter().map(HeaderName::as_str),
+        );
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ProblemDetails, RicksponseConfig};
+    use actix_web::test::TestRequest;
+    use actix_web::{body, http::header, Responder};
+    use http::StatusCode;
+    use simple_serde::ContentType;
+
+    #[test]
+    fn test_problem_serialization() {
+        let problem = ProblemDetails::new(404)
+            .detail("No order with id 7.")
+            .extension("order", 7);
+
+        assert_eq!(
+            serde_json::to_value(&problem).unwrap(),
+            serde_json::json!({
+                "type": "about:blank",
+                "title": "Not Found",
+                "status": 404,
+                "detail": "No order with id 7.",
+                "order": 7,
+            })
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_problem_media_types() {
+        for (accept, content_type) in [
+            ("application/json", "application/problem+json"),
+            ("application/cbor", "application/problem+cbor"),
+            ("application/toml", "application/toml"),
+            ("text/html", "application/problem+json"),
+        ] {
+            let req = TestRequest::default()
+                .insert_header((header::ACCEPT, accept))
+                .to_http_request();
+
+            let res = ProblemDetails::new(400).respond_to(&req);
+
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+            assert_eq!(
+                res.headers().get(header::CONTENT_TYPE).unwrap(),
+                content_type
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_problem_uses_configured_content_types() {
+        let req = TestRequest::default()
+            .app_data(RicksponseConfig::default().default_content_type(ContentType::Yaml))
+            .to_http_request();
+
+        let res = ProblemDetails::new(400).respond_to(&req);
+
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+yaml"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_problem_extensions_do_not_shadow_members() {
+        let req = TestRequest::default().to_http_request();
+
+        let res = ProblemDetails::new(400)
+            .extension("status", 200)
+            .respond_to(&req);
+        let body = body::to_bytes(res.into_body()).await.unwrap();
+
+        let value = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+        assert_eq!(value["status"], 400);
+
+        let problem = ProblemDetails::new(400).extension("title", "Shadowed");
+        let json = serde_json::to_string(&problem).unwrap();
+        assert_eq!(json.matches(r#""title""#).count(), 1);
+        assert!(!json.contains("Shadowed"));
+    }
+}
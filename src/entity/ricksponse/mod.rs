@@ -1 +1,5 @@
+pub mod parts;
+#[allow(clippy::module_inception)]
 pub mod ricksponse;
+pub mod sse;
+pub mod stream;
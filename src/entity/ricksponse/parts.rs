@@ -0,0 +1,193 @@
+use crate::entity::api_error::error_response;
+use crate::entity::conditional::ETagSource;
+use crate::entity::envelope::Envelope;
+use crate::entity::negotiation::append_vary;
+use crate::entity::negotiation::MediaType;
+use crate::entity::payload_control::DefaultControl;
+use crate::entity::problem_details::ProblemDetails;
+use crate::error::Error;
+use actix_http::error::HttpError;
+use actix_http::header::{HeaderMap, HeaderName, TryIntoHeaderPair, VARY};
+use actix_web::cookie::Cookie;
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{HttpRequest, HttpResponse};
+use serde_json::{Map, Value};
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use std::mem;
+
+/// Everything about a `Ricksponse` that lives next to the payload rather than in it.
+///
+/// `C` is the `PayloadControl` the payload is extracted with.
+pub struct Parts<C = DefaultControl> {
+    pub(crate) media_type: Option<MediaType>,
+    pub(crate) problem: Option<ProblemDetails>,
+    headers: HeaderMap,
+    cookies: Vec<Cookie<'static>>,
+    header_error: Option<HttpError>,
+    /// `None` follows the `RicksponseConfig`, `Some(None)` opts out of its envelope.
+    pub(crate) envelope: Option<Option<Envelope>>,
+    meta: Map<String, Value>,
+    pub(crate) etag: Option<ETagSource>,
+    cache_control: Vec<CacheDirective>,
+    vary: Vec<HeaderName>,
+    control: PhantomData<fn() -> C>,
+}
+
+impl<C> Parts<C> {
+    /// The media type of the request body the payload was extracted from, parameters included.
+    pub fn media_type(&self) -> Option<&MediaType> {
+        self.media_type.as_ref()
+    }
+
+    /// The problem details an error response is rendered from.
+    pub fn problem(&self) -> Option<&ProblemDetails> {
+        self.problem.as_ref()
+    }
+
+    /// The headers added to the response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The cookies added to the response.
+    pub fn cookies(&self) -> &[Cookie<'static>] {
+        &self.cookies
+    }
+
+    /// Insert a header into the response, replacing any header of the same name.
+    ///
+    /// A header that fails to convert turns the response into a `500 Internal Server Error`.
+    pub fn insert_header(&mut self, header: impl TryIntoHeaderPair) {
+        match header.try_into_pair() {
+            Ok((name, value)) => {
+                self.headers.insert(name, value);
+            }
+            Err(e) => {
+                self.header_error.get_or_insert(e.into());
+            }
+        }
+    }
+
+    /// Add a cookie to the response.
+    pub fn add_cookie(&mut self, cookie: Cookie<'static>) {
+        self.cookies.push(cookie);
+    }
+
+    /// The meta data rendered in the envelope of the response.
+    pub fn meta(&self) -> &Map<String, Value> {
+        &self.meta
+    }
+
+    /// Add an entry to the meta data of the envelope.
+    pub fn insert_meta(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.meta.insert(key.into(), value.into());
+    }
+
+    /// The directives of the `Cache-Control` header of the response.
+    pub fn cache_control(&self) -> &[CacheDirective] {
+        &self.cache_control
+    }
+
+    /// Add a directive to the `Cache-Control` header of the response.
+    ///
+    /// The directive replaces an earlier one of the same kind, so setting `max-age` twice keeps
+    /// the last value and `private` replaces `public`.
+    pub fn add_cache_directive(&mut self, directive: CacheDirective) {
+        self.cache_control
+            .retain(|d| !same_directive(d, &directive));
+        self.cache_control.push(directive);
+    }
+
+    /// The request headers the response varies on, besides the ones negotiation looked at.
+    pub fn vary(&self) -> &[HeaderName] {
+        &self.vary
+    }
+
+    /// Add a request header to the `Vary` header of the response.
+    pub fn add_vary(&mut self, name: HeaderName) {
+        if !self.vary.contains(&name) {
+            self.vary.push(name);
+        }
+    }
+
+    /// Apply the headers and cookies to the response.
+    ///
+    /// The headers are inserted last, so they override the headers set by the responder, such as
+    /// the negotiated `Content-Type`. A `Vary` header is merged with the one of the responder
+    /// instead, as the negotiated response still varies on `Accept`.
+    pub(crate) fn finish(self, mut res: HttpResponse, req: &HttpRequest) -> HttpResponse {
+        if let Some(e) = self.header_error {
+            return error_response(&Error::InvalidHeader(e), req);
+        }
+        if !self.cache_control.is_empty() {
+            match CacheControl(self.cache_control).try_into_pair() {
+                Ok((name, value)) => {
+                    res.headers_mut().insert(name, value);
+                }
+                Err(e) => return error_response(&Error::InvalidHeader(e.into()), req),
+            }
+        }
+        for (name, value) in self.headers {
+            if name == VARY {
+                let names = value.to_str().unwrap_or_default().split(',').map(str::trim);
+                append_vary(res.headers_mut(), names);
+            } else {
+                res.headers_mut().insert(name, value);
+            }
+        }
+        append_vary(res.headers_mut(), self.vary.iter().map(HeaderName::as_str));
+        for cookie in &self.cookies {
+            if let Err(e) = res.add_cookie(cookie) {
+                return error_response(&Error::InvalidHeader(e), req);
+            }
+        }
+        res
+    }
+}
+
+impl<C> Default for Parts<C> {
+    fn default() -> Self {
+        Parts {
+            media_type: None,
+            problem: None,
+            headers: HeaderMap::new(),
+            cookies: Vec::new(),
+            header_error: None,
+            envelope: None,
+            meta: Map::new(),
+            etag: None,
+            cache_control: Vec::new(),
+            vary: Vec::new(),
+            control: PhantomData,
+        }
+    }
+}
+
+impl<C> Debug for Parts<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Parts")
+            .field("media_type", &self.media_type)
+            .field("problem", &self.problem)
+            .field("headers", &self.headers)
+            .field("cookies", &self.cookies)
+            .field("envelope", &self.envelope)
+            .field("meta", &self.meta)
+            .field("etag", &self.etag)
+            .field("cache_control", &self.cache_control)
+            .field("vary", &self.vary)
+            .finish()
+    }
+}
+
+/// Whether `b` replaces `a` in a `Cache-Control` header.
+fn same_directive(a: &CacheDirective, b: &CacheDirective) -> bool {
+    match (a, b) {
+        (CacheDirective::Extension(a, _), CacheDirective::Extension(b, _)) => a == b,
+        (
+            CacheDirective::Public | CacheDirective::Private,
+            CacheDirective::Public | CacheDirective::Private,
+        ) => true,
+        _ => mem::discriminant(a) == mem::discriminant(b),
+    }
+}
@@ -1,34 +1,48 @@
-use crate::entity::payload_control::PayloadControl;
-use crate::entity::payload_future::PayloadFuture;
-use crate::error::Error;
+use crate::entity::api_error::{error_response, ApiError};
+use crate::entity::conditional::{self, ETagSource, Precondition};
+use crate::entity::envelope::{Envelope, Enveloped};
+use crate::entity::negotiation::{append_vary, negotiate, MediaType, NEGOTIATION_HEADERS};
+use crate::entity::payload_control::{DefaultControl, PayloadControl};
+use crate::entity::payload_error::{accept_header, PayloadError};
+use crate::entity::payload_future::{FromPayload, PayloadFuture};
+use crate::entity::problem_details::ProblemDetails;
+use crate::entity::ricksponse::parts::Parts;
+use crate::entity::ricksponse_config::RicksponseConfig;
+use crate::entity::validation::{Validate, Validated};
 use actix_http::body::BoxBody;
+use actix_http::header::TryIntoHeaderPair;
+use actix_web::cookie::Cookie;
+use actix_web::http::header::{
+    CacheDirective, ETag, EntityTag, HeaderName, HttpDate, LastModified,
+};
 use actix_web::{FromRequest, HttpRequest, HttpResponse, HttpResponseBuilder, Responder};
 use http::StatusCode;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use simple_serde::{ContentType, SimpleEncoder};
-use std::any::Any;
+use serde_json::Value;
+use simple_serde::SimpleEncoder;
 use std::fmt::{Debug, Display, Formatter};
 use std::hint;
 use std::marker::PhantomData;
-
-pub trait DebuggableAny: Debug + Any {}
+use std::mem;
 
 #[derive()]
-pub enum Ricksponse<T> {
+pub enum Ricksponse<T, C = DefaultControl> {
     Data {
         data: T,
         http_code: Option<u16>,
         message: Option<String>,
+        parts: Parts<C>,
     },
     Error {
-        error: Option<Box<dyn DebuggableAny>>,
+        error: Option<Box<dyn ApiError>>,
         http_code: Option<u16>,
         message: Option<String>,
+        parts: Parts<C>,
     },
 }
 
-impl<T> Ricksponse<T> {
+impl<T, C> Ricksponse<T, C> {
     pub fn http_code(&mut self, code: u16) {
         match self {
             Ricksponse::Data { http_code, .. } => *http_code = Some(code),
@@ -42,15 +56,220 @@ impl<T> Ricksponse<T> {
         }
     }
 
+    pub fn parts(&self) -> &Parts<C> {
+        match self {
+            Ricksponse::Data { parts, .. } => parts,
+            Ricksponse::Error { parts, .. } => parts,
+        }
+    }
+
+    pub fn parts_mut(&mut self) -> &mut Parts<C> {
+        match self {
+            Ricksponse::Data { parts, .. } => parts,
+            Ricksponse::Error { parts, .. } => parts,
+        }
+    }
+
+    /// The media type of the request body this payload was extracted from, eg.
+    /// `application/vnd.acme.order+json; version=2`.
+    pub fn media_type(&self) -> Option<&MediaType> {
+        self.parts().media_type()
+    }
+
+    /// Insert a header into the response, replacing any header of the same name.
+    ///
+    /// ```rust
+    /// use actix_web::http::header::{CacheControl, CacheDirective, LOCATION};
+    /// use ricksponse::Ricksponse;
+    ///
+    /// let response = Ricksponse::CREATED("order".to_string())
+    ///     .header((LOCATION, "/orders/7"))
+    ///     .header(CacheControl(vec![CacheDirective::NoStore]));
+    /// ```
+    pub fn header(mut self, header: impl TryIntoHeaderPair) -> Self {
+        self.parts_mut().insert_header(header);
+        self
+    }
+
+    /// Insert several headers into the response, see `header`.
+    pub fn insert_headers<H: TryIntoHeaderPair>(
+        mut self,
+        headers: impl IntoIterator<Item = H>,
+    ) -> Self {
+        for header in headers {
+            self.parts_mut().insert_header(header);
+        }
+        self
+    }
+
+    /// Add a cookie to the response.
+    pub fn cookie(mut self, cookie: Cookie<'static>) -> Self {
+        self.parts_mut().add_cookie(cookie);
+        self
+    }
+
+    /// Wrap the data of this response in `envelope`, regardless of the `RicksponseConfig`.
+    pub fn envelope(mut self, envelope: Envelope) -> Self {
+        self.parts_mut().envelope = Some(Some(envelope));
+        self
+    }
+
+    /// Respond with the bare data, even when the `RicksponseConfig` sets an envelope.
+    pub fn without_envelope(mut self) -> Self {
+        self.parts_mut().envelope = Some(None);
+        self
+    }
+
+    /// Add an entry to the meta data of the envelope.
+    pub fn meta(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.parts_mut().insert_meta(key, value);
+        self
+    }
+
+    /// Tag the response with a strong `ETag` computed from the encoded body.
+    ///
+    /// `GET` and `HEAD` requests whose `If-None-Match` matches the tag are answered with
+    /// `304 Not Modified`, those whose `If-Match` does not match with
+    /// `412 Precondition Failed`. A `Last-Modified` header is evaluated against
+    /// `If-Modified-Since` and `If-Unmodified-Since` the same way. The preconditions of state
+    /// changing requests are evaluated by the handler before making the change, see
+    /// `evaluate_preconditions`.
+    ///
+    /// ```rust
+    /// use ricksponse::Ricksponse;
+    ///
+    /// let response = Ricksponse::OK("order".to_string()).etag();
+    /// ```
+    pub fn etag(mut self) -> Self {
+        self.parts_mut().etag = Some(ETagSource::Strong);
+        self
+    }
+
+    /// Tag the response with a weak `ETag` computed from the encoded body, see `etag`.
+    pub fn weak_etag(mut self) -> Self {
+        self.parts_mut().etag = Some(ETagSource::Weak);
+        self
+    }
+
+    /// Tag the response with an `ETag` the application keeps, eg. the version of a row, see
+    /// `etag`. The body does not need to be hashed for it.
+    ///
+    /// ```rust
+    /// use actix_web::http::header::EntityTag;
+    /// use ricksponse::Ricksponse;
+    ///
+    /// let response = Ricksponse::OK("order".to_string())
+    ///     .entity_tag(EntityTag::new_weak("v7".to_string()));
+    /// ```
+    pub fn entity_tag(mut self, tag: EntityTag) -> Self {
+        self.parts_mut().etag = Some(ETagSource::Version(tag));
+        self
+    }
+
+    /// Let caches reuse the response for `seconds`, the `max-age` directive of `Cache-Control`.
+    ///
+    /// ```rust
+    /// use actix_web::http::header::ACCEPT_LANGUAGE;
+    /// use ricksponse::Ricksponse;
+    ///
+    /// let response = Ricksponse::OK("order".to_string())
+    ///     .private()
+    ///     .max_age(60)
+    ///     .stale_while_revalidate(30)
+    ///     .vary(ACCEPT_LANGUAGE);
+    /// ```
+    pub fn max_age(self, seconds: u32) -> Self {
+        self.cache_directive(CacheDirective::MaxAge(seconds))
+    }
+
+    /// Let only the cache of the client store the response, replaces `public`.
+    pub fn private(self) -> Self {
+        self.cache_directive(CacheDirective::Private)
+    }
+
+    /// Let shared caches store the response as well, replaces `private`.
+    pub fn public(self) -> Self {
+        self.cache_directive(CacheDirective::Public)
+    }
+
+    /// Let caches serve the response for `seconds` after it went stale while they revalidate it
+    /// in the background, RFC 5861.
+    pub fn stale_while_revalidate(self, seconds: u32) -> Self {
+        self.cache_directive(CacheDirective::Extension(
+            "stale-while-revalidate".to_string(),
+            Some(seconds.to_string()),
+        ))
+    }
+
+    /// Add any other directive to the `Cache-Control` header, eg. `CacheDirective::NoStore`.
+    pub fn cache_directive(mut self, directive: CacheDirective) -> Self {
+        self.parts_mut().add_cache_directive(directive);
+        self
+    }
+
+    /// Set the `Last-Modified` header, which is evaluated against `If-Modified-Since` and
+    /// `If-Unmodified-Since` like the `ETag` is, see `etag`.
+    pub fn last_modified(self, date: impl Into<HttpDate>) -> Self {
+        self.header(LastModified(date.into()))
+    }
+
+    /// Add a request header the response varies on to the `Vary` header. `Accept` is added to
+    /// every negotiated response without asking.
+    pub fn vary(mut self, name: HeaderName) -> Self {
+        self.parts_mut().add_vary(name);
+        self
+    }
+
+    /// Returns `true` if this is an error of type `E`.
+    pub fn is<E: ApiError>(&self) -> bool {
+        self.downcast_ref::<E>().is_some()
+    }
+
+    /// Returns a reference to the error if this is an error of type `E`.
+    pub fn downcast_ref<E: ApiError>(&self) -> Option<&E> {
+        match self {
+            Ricksponse::Error {
+                error: Some(error), ..
+            } => error.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// Takes the error out if this is an error of type `E`, gives the `Ricksponse` back otherwise.
+    #[allow(clippy::result_large_err)]
+    pub fn downcast<E: ApiError>(self) -> Result<E, Self> {
+        match self {
+            Ricksponse::Error {
+                error: Some(error),
+                http_code,
+                message,
+                parts,
+            } => match error.downcast::<E>() {
+                Ok(error) => Ok(*error),
+                Err(error) => Err(Ricksponse::Error {
+                    error: Some(error),
+                    http_code,
+                    message,
+                    parts,
+                }),
+            },
+            ricksponse => Err(ricksponse),
+        }
+    }
+
     pub fn get_or_with_data(&mut self, data: T) -> &mut T {
         if let Ricksponse::Error {
-            http_code, message, ..
+            http_code,
+            message,
+            parts,
+            ..
         } = self
         {
             *self = Ricksponse::Data {
                 data,
                 http_code: *http_code,
                 message: message.clone(),
+                parts: std::mem::take(parts),
             }
         }
         // SAFETY: a `None` variant for `self` would have been replaced by a `Some`
@@ -60,12 +279,15 @@ impl<T> Ricksponse<T> {
             Ricksponse::Error { .. } => unsafe { hint::unreachable_unchecked() },
         }
     }
+}
 
+impl<T> Ricksponse<T> {
     pub fn new(t: T) -> Self {
         Self::Data {
             data: t,
             http_code: None,
             message: None,
+            parts: Parts::default(),
         }
     }
 
@@ -74,6 +296,7 @@ impl<T> Ricksponse<T> {
             data: t,
             http_code: Some(http),
             message: None,
+            parts: Parts::default(),
         }
     }
 
@@ -82,32 +305,56 @@ impl<T> Ricksponse<T> {
             data: t,
             http_code: Some(http),
             message: Some(message.to_string()),
+            parts: Parts::default(),
         }
     }
 }
 
-impl<T, E: DebuggableAny> From<Result<T, E>> for Ricksponse<T> {
+impl<T, C, E: ApiError> From<Result<T, E>> for Ricksponse<T, C> {
     fn from(r: Result<T, E>) -> Self {
-        let message = if let Err(e) = &r {
-            Some(format!("{:?}", e))
-        } else {
-            None
-        };
         match r {
             Err(e) => Self::Error {
+                http_code: Some(e.status()),
+                message: e.message(),
                 error: Some(Box::new(e)),
-                http_code: None,
-                message,
+                parts: Parts::default(),
             },
             Ok(t) => Self::Data {
                 data: t,
                 http_code: None,
-                message,
+                message: None,
+                parts: Parts::default(),
             },
         }
     }
 }
 
+impl<T, C> From<ProblemDetails> for Ricksponse<T, C> {
+    fn from(problem: ProblemDetails) -> Self {
+        let mut parts = Parts::default();
+        let http_code = problem.status;
+        let message = problem.detail.clone();
+        parts.problem = Some(problem);
+        Self::Error {
+            error: None,
+            http_code,
+            message,
+            parts,
+        }
+    }
+}
+
+impl<T, C> FromPayload<T> for Ricksponse<T, C> {
+    fn from_payload(
+        res: Result<T, PayloadError>,
+        media_type: Option<MediaType>,
+    ) -> Result<Self, PayloadError> {
+        let mut ricksponse = Self::from(res);
+        ricksponse.parts_mut().media_type = media_type;
+        Ok(ricksponse)
+    }
+}
+
 // impl<T: Serialize> From<HateoasResponse<T>> for Ricksponse<HateoasResponse<T>> {
 //     fn from(r: HateoasResponse<T>) -> Self {
 //         let http_code = r.status.as_ref().and_then(|status| status.http_status_code);
@@ -120,8 +367,8 @@ impl<T, E: DebuggableAny> From<Result<T, E>> for Ricksponse<T> {
 //     }
 // }
 
-impl<T> From<Ricksponse<T>> for Result<T, Option<Box<dyn DebuggableAny>>> {
-    fn from(r: Ricksponse<T>) -> Self {
+impl<T, C> From<Ricksponse<T, C>> for Result<T, Option<Box<dyn ApiError>>> {
+    fn from(r: Ricksponse<T, C>) -> Self {
         match r {
             Ricksponse::Data { data, .. } => Ok(data),
             Ricksponse::Error { error, .. } => Err(error),
@@ -143,7 +390,7 @@ impl<T> From<Ricksponse<T>> for Result<T, Option<Box<dyn DebuggableAny>>> {
 //     }
 // }
 
-impl<T> Debug for Ricksponse<T>
+impl<T, C> Debug for Ricksponse<T, C>
 where
     T: Debug,
 {
@@ -153,6 +400,7 @@ where
                 data,
                 http_code,
                 message,
+                ..
             } => {
                 write!(
                     f,
@@ -164,6 +412,7 @@ where
                 error,
                 http_code,
                 message,
+                ..
             } => {
                 write!(
                     f,
@@ -175,7 +424,7 @@ where
     }
 }
 
-impl<T> Display for Ricksponse<T>
+impl<T, C> Display for Ricksponse<T, C>
 where
     T: Display,
 {
@@ -200,12 +449,10 @@ where
 //     }
 // }
 
-impl<T> From<Ricksponse<T>> for HttpResponseBuilder {
-    fn from(r: Ricksponse<T>) -> Self {
+impl<T, C> From<Ricksponse<T, C>> for HttpResponseBuilder {
+    fn from(r: Ricksponse<T, C>) -> Self {
         match r {
-            Ricksponse::Data {
-                data, http_code, ..
-            } => {
+            Ricksponse::Data { http_code, .. } => {
                 let response_code = match http_code {
                     Some(code) => StatusCode::from_u16(code).unwrap_or(StatusCode::OK),
                     None => StatusCode::OK,
@@ -222,47 +469,92 @@ impl<T> From<Ricksponse<T>> for HttpResponseBuilder {
     }
 }
 
-impl<T: Serialize> Responder for Ricksponse<T> {
+impl<T: Serialize, C> Responder for Ricksponse<T, C> {
     type Body = BoxBody;
 
     fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
-        let mut content_type_collection = req
-            .headers()
-            .get_all("Accept")
-            .filter_map(|h| ContentType::try_from(h).ok())
-            .collect::<Vec<ContentType>>();
-        if content_type_collection.is_empty() {
-            content_type_collection = vec![ContentType::Json];
-        }
         match self {
             Ricksponse::Data {
-                data, http_code, ..
+                data,
+                http_code,
+                message,
+                mut parts,
             } => {
                 let response_code = match http_code {
                     Some(code) => StatusCode::from_u16(code).unwrap_or(StatusCode::OK),
                     None => StatusCode::OK,
                 };
-                content_type_collection.reverse();
-                content_type_collection
-                    .pop()
-                    .ok_or_else(|| HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR))
-                    .and_then(|content_type| {
-                        data.encode(&content_type)
-                            .map(|t| {
+                let config = RicksponseConfig::from_req(req);
+                let envelope = match parts.envelope.take() {
+                    Some(envelope) => envelope,
+                    None => config.response_envelope().cloned(),
+                };
+                let mut res = match negotiate(req.headers(), config.allowed_content_types()) {
+                    Ok(negotiated) => {
+                        let encoded = match &envelope {
+                            Some(envelope) => Enveloped {
+                                envelope,
+                                data: &data,
+                                message: message.as_deref(),
+                                status: response_code.as_u16(),
+                                meta: parts.meta(),
+                            }
+                            .encode(negotiated.content_type()),
+                            None => data.encode(negotiated.content_type()),
+                        };
+                        match encoded {
+                            Ok(mut t) => {
+                                let body = mem::take(&mut *t);
+                                if let Some(etag) = parts.etag.take() {
+                                    parts.insert_header(ETag(etag.entity_tag(&body)));
+                                }
                                 HttpResponseBuilder::new(response_code)
-                                    .content_type(content_type)
-                                    .body(t.to_vec())
-                            })
-                            .map_err(|_| HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR))
-                    })
-                    .unwrap_or_else(|e| e)
+                                    .content_type(negotiated.media_type().to_string())
+                                    .body(body)
+                            }
+                            Err(e) => error_response(&PayloadError::Serialize(e), req),
+                        }
+                    }
+                    Err(e) => error_response(&e, req),
+                };
+                append_vary(
+                    res.headers_mut(),
+                    NEGOTIATION_HEADERS.iter().map(HeaderName::as_str),
+                );
+                let res = parts.finish(res, req);
+                match conditional::evaluate_response(req, &res) {
+                    Precondition::Passed => res,
+                    Precondition::NotModified => conditional::not_modified(res),
+                    Precondition::Failed => error_response(&crate::Error::PreconditionFailed, req),
+                }
+            }
+            Ricksponse::Error {
+                error,
+                http_code,
+                message,
+                mut parts,
+            } => {
+                let accept = error.as_deref().and_then(|e| accept_header(e, req));
+                let mut problem = parts
+                    .problem
+                    .take()
+                    .or_else(|| error.map(|e| e.problem()))
+                    .unwrap_or_else(|| {
+                        ProblemDetails::new(StatusCode::INTERNAL_SERVER_ERROR.as_u16())
+                    });
+                // The status code and message of the `Ricksponse` win over those of the problem.
+                if let Some(code) = http_code {
+                    problem = problem.status(code);
+                }
+                if message.is_some() {
+                    problem.detail = message;
+                }
+                let mut res = problem.respond_to(req);
+                if let Some((name, value)) = accept {
+                    res.headers_mut().insert(name, value);
+                }
+                parts.finish(res, req)
             }
-            Ricksponse::Error { http_code, .. } => match http_code {
-                Some(code) => HttpResponse::new(
-                    StatusCode::from_u16(code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
-                ),
-                None => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
-            },
         }
     }
 }
@@ -280,9 +572,11 @@ impl<T: Serialize> Responder for Ricksponse<T> {
 ///     pub username: String,
 /// }
 ///
-/// impl PayloadControl for Info {
-///     const MAX_PAYLOAD_SIZE: Option<usize> = None;
-///     const BUFFER_CAPACITY: Option<usize> = None;
+/// /// limits for the routes that take larger bodies
+/// struct Large;
+///
+/// impl PayloadControl for Large {
+///     const MAX_PAYLOAD_SIZE: Option<usize> = Some(104_857_600);
 /// }
 ///
 /// /// deserialize `Info` from request's body
@@ -290,20 +584,25 @@ impl<T: Serialize> Responder for Ricksponse<T> {
 /// format!("Welcome {:?}!", info)
 /// }
 ///
+/// /// deserialize `Info` from request's body with the limits of `Large`
+/// async fn import(info: Ricksponse<Info, Large>) -> String {
+/// format!("Imported {:?}!", info)
+/// }
+///
 /// fn main() {
-///     let app = App::new().service(
-///         web::resource("/index.html").route(
-///            web::post().to(index))
-///     );
+///     let app = App::new()
+///         .service(web::resource("/index.html").route(web::post().to(index)))
+///         .service(web::resource("/import").route(web::post().to(import)));
 /// }
 /// ```
 ///
-impl<T> FromRequest for Ricksponse<T>
+impl<T, C> FromRequest for Ricksponse<T, C>
 where
-    T: DeserializeOwned + PayloadControl,
+    T: DeserializeOwned,
+    C: PayloadControl,
 {
-    type Error = Error;
-    type Future = PayloadFuture<T, T, Ricksponse<T>>;
+    type Error = actix_web::Error;
+    type Future = PayloadFuture<C, T, Ricksponse<T, C>>;
 
     #[inline]
     fn from_request(req: &HttpRequest, payload: &mut actix_http::Payload) -> Self::Future {
@@ -311,6 +610,20 @@ where
     }
 }
 
+impl<T, C> FromRequest for Ricksponse<T, Validated<C>>
+where
+    T: DeserializeOwned + Validate,
+    C: PayloadControl,
+{
+    type Error = actix_web::Error;
+    type Future = PayloadFuture<C, T, Ricksponse<T, Validated<C>>>;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, payload: &mut actix_http::Payload) -> Self::Future {
+        PayloadFuture::validated(req.clone(), payload)
+    }
+}
+
 pub struct Response<T> {
     phantom: PhantomData<T>,
 }
@@ -362,6 +675,7 @@ macro_rules! auto_impl_response {
         )+
         }
 
+        #[allow(dead_code)]
         fn canonical_reason(num: u16) -> Option<&'static str> {
             match num {
                 $(
@@ -569,13 +883,48 @@ auto_impl_response! {
 
 #[cfg(test)]
 mod test {
+    use crate::ApiError;
+    use crate::ContentType;
+    use crate::Envelope;
+    use crate::PayloadControl;
+    use crate::ProblemDetails;
     use crate::Response;
     use crate::Ricksponse;
+    use crate::RicksponseConfig;
+    use actix_web::cookie::Cookie;
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::{http::header, web, App, Responder};
+    use http::StatusCode;
+    use simple_serde::{Decoded, SimpleDecoder};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Order {
+        id: u32,
+    }
+
+    #[derive(Debug)]
+    struct OutOfStock;
+
+    impl ApiError for OutOfStock {
+        fn status(&self) -> u16 {
+            409
+        }
+
+        fn code(&self) -> Option<&str> {
+            Some("order.out_of_stock")
+        }
+    }
+
+    struct Tiny;
+
+    impl PayloadControl for Tiny {
+        const MAX_PAYLOAD_SIZE: Option<usize> = Some(4);
+    }
 
     #[test]
     fn test_status_codes() {
-        let ricksponse_continue = Ricksponse::CONTINUE(());
-        let response_continue = Response::CONTINUE(());
+        let _ricksponse_continue = Ricksponse::CONTINUE(());
+        let _response_continue = Response::CONTINUE(());
     }
 
     #[actix_web::test]
@@ -589,4 +938,427 @@ mod test {
             &"test".to_string()
         );
     }
+
+    #[actix_web::test]
+    async fn test_respond_with_weighted_accept() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/json;q=0.5, application/yaml"))
+            .to_http_request();
+
+        let res = Ricksponse::OK("test".to_string()).respond_to(&req);
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/yaml"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_respond_not_acceptable() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "text/html, application/json;q=0"))
+            .to_http_request();
+
+        let res = Ricksponse::OK("test".to_string()).respond_to(&req);
+
+        assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_vendor_media_type_round_trip() {
+        let app = init_service(App::new().route(
+            "/orders",
+            web::post().to(|order: Ricksponse<Order>| async move {
+                let media_type = order.media_type().unwrap().to_string();
+                let mut order = order;
+                Ricksponse::OK(format!(
+                    "{} {}",
+                    order.get_or_with_data(Order { id: 0 }).id,
+                    media_type
+                ))
+            }),
+        ))
+        .await;
+
+        let vendor_type = "application/vnd.acme.order+json; version=2";
+        let req = TestRequest::post()
+            .uri("/orders")
+            .insert_header((header::CONTENT_TYPE, vendor_type))
+            .insert_header((header::ACCEPT, vendor_type))
+            .set_payload(r#"{"id":7}"#)
+            .to_request();
+        let res = call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            vendor_type
+        );
+        let body = read_body(res).await;
+        assert_eq!(
+            serde_json::from_slice::<String>(&body).unwrap(),
+            format!("7 {}", vendor_type)
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_control_marker_overrides_limit() {
+        let app = init_service(
+            App::new()
+                .route(
+                    "/default",
+                    web::post().to(|order: Ricksponse<Order>| async move {
+                        matches!(order, Ricksponse::Data { .. }).to_string()
+                    }),
+                )
+                .route(
+                    "/tiny",
+                    web::post().to(|order: Ricksponse<Order, Tiny>| async move {
+                        matches!(order, Ricksponse::Data { .. }).to_string()
+                    }),
+                ),
+        )
+        .await;
+
+        for (uri, extracted) in [("/default", "true"), ("/tiny", "false")] {
+            let req = TestRequest::post()
+                .uri(uri)
+                .insert_header(header::ContentType::json())
+                .set_payload(r#"{"id":7}"#)
+                .to_request();
+            let body = read_body(call_service(&app, req).await).await;
+            assert_eq!(body, extracted);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_unsupported_content_type_advertises_accepted_types() {
+        let app = init_service(
+            App::new()
+                .app_data(RicksponseConfig::default().content_types(vec![ContentType::Json]))
+                .route(
+                    "/orders",
+                    web::post().to(|order: Ricksponse<Order>| async move { order }),
+                ),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/orders")
+            .insert_header((header::CONTENT_TYPE, "application/yaml"))
+            .set_payload("id: 7")
+            .to_request();
+        let res = call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(
+            res.headers().get("accept-post").unwrap(),
+            "application/json"
+        );
+        let body = read_body(res).await;
+        assert_eq!(
+            serde_json::from_slice::<ProblemDetails>(&body)
+                .unwrap()
+                .extensions["details"],
+            serde_json::json!(["application/json"])
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_error_renders_problem_details() {
+        let req = TestRequest::default().uri("/orders/7").to_http_request();
+
+        let res = Ricksponse::<Order>::from(Err::<Order, _>(OutOfStock)).respond_to(&req);
+
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+        let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<ProblemDetails>(&body).unwrap(),
+            ProblemDetails::new(409)
+                .instance("/orders/7")
+                .extension("code", "order.out_of_stock")
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_error_from_problem_details() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/yaml"))
+            .to_http_request();
+
+        let problem = ProblemDetails::new(403)
+            .problem_type("https://example.com/probs/out-of-credit")
+            .instance("/account/12345")
+            .extension("balance", 30);
+        let res = Ricksponse::<Order>::from(problem.clone()).respond_to(&req);
+
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+yaml"
+        );
+        let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+        let decoded: Decoded<ProblemDetails> = body.to_vec().decode("yaml").unwrap();
+        assert_eq!(decoded.into(), problem);
+    }
+
+    #[test]
+    fn test_downcast_error() {
+        let ricksponse = Ricksponse::<Order>::from(Err::<Order, _>(OutOfStock));
+
+        assert!(ricksponse.is::<OutOfStock>());
+        assert!(!ricksponse.is::<crate::PayloadError>());
+        assert!(ricksponse.downcast_ref::<OutOfStock>().is_some());
+
+        let ricksponse = ricksponse.downcast::<crate::PayloadError>().unwrap_err();
+        assert!(matches!(
+            ricksponse.downcast::<OutOfStock>(),
+            Ok(OutOfStock)
+        ));
+
+        let data = Ricksponse::new(Order { id: 7 });
+        assert!(!data.is::<OutOfStock>());
+        assert!(data.downcast::<OutOfStock>().is_err());
+    }
+
+    #[test]
+    fn test_ricksponse_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Ricksponse<Order>>();
+        assert_send_sync::<Ricksponse<Order, Tiny>>();
+    }
+
+    #[actix_web::test]
+    async fn test_response_headers_and_cookies() {
+        let req = TestRequest::default().to_http_request();
+
+        let res = Ricksponse::CREATED(Order { id: 7 })
+            .header((header::LOCATION, "/orders/7"))
+            .insert_headers([("x-trace-id", "abc"), ("x-span-id", "def")])
+            .cookie(Cookie::new("session", "s3cr3t"))
+            .respond_to(&req);
+
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.headers().get(header::LOCATION).unwrap(), "/orders/7");
+        assert_eq!(res.headers().get("x-trace-id").unwrap(), "abc");
+        assert_eq!(res.headers().get("x-span-id").unwrap(), "def");
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+        assert_eq!(res.cookies().next().unwrap().value(), "s3cr3t");
+
+        let res = Ricksponse::<Order>::from(Err::<Order, _>(OutOfStock))
+            .header((header::RETRY_AFTER, "120"))
+            .respond_to(&req);
+
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        assert_eq!(res.headers().get(header::RETRY_AFTER).unwrap(), "120");
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_response_header() {
+        let req = TestRequest::default().to_http_request();
+
+        let res = Ricksponse::OK(Order { id: 7 })
+            .header(("x-trace-id", "line\nbreak"))
+            .respond_to(&req);
+
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[actix_web::test]
+    async fn test_envelope() {
+        let req = TestRequest::default().to_http_request();
+
+        let res = Ricksponse::OK(Order { id: 7 })
+            .envelope(Envelope::default())
+            .meta("page", 1)
+            .respond_to(&req);
+        let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            serde_json::json!({
+                "status": 200,
+                "message": "OK",
+                "data": { "id": 7 },
+                "meta": { "page": 1 },
+            })
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_configured_envelope() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/toml"))
+            .app_data(RicksponseConfig::default().envelope(Envelope::default().data("result")))
+            .to_http_request();
+
+        let res = Ricksponse::new(Order { id: 7 }).respond_to(&req);
+        let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, "status = 200\n\n[result]\nid = 7\n");
+
+        let res = Ricksponse::new(Order { id: 7 })
+            .without_envelope()
+            .respond_to(&req);
+        let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, "id = 7\n");
+    }
+
+    #[actix_web::test]
+    async fn test_etag_not_modified() {
+        let req = TestRequest::default().to_http_request();
+        let res = Ricksponse::OK(Order { id: 7 }).etag().respond_to(&req);
+        let etag = res.headers().get(header::ETAG).unwrap().clone();
+        assert_eq!(etag, r#""8-d51421b3d1d9d8b9""#);
+
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, etag.clone()))
+            .to_http_request();
+        let res = Ricksponse::OK(Order { id: 7 })
+            .etag()
+            .header((header::CACHE_CONTROL, "max-age=60"))
+            .respond_to(&req);
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers().get(header::ETAG).unwrap(), etag);
+        assert_eq!(
+            res.headers().get(header::CACHE_CONTROL).unwrap(),
+            "max-age=60"
+        );
+        assert!(res.headers().get(header::CONTENT_TYPE).is_none());
+        let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+        assert!(body.is_empty());
+
+        let res = Ricksponse::OK(Order { id: 8 }).etag().respond_to(&req);
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_entity_tag_precondition_failed() {
+        let req = TestRequest::get()
+            .insert_header((header::IF_MATCH, r#""v6""#))
+            .insert_header((header::ACCEPT, "application/yaml"))
+            .to_http_request();
+
+        let res = Ricksponse::OK(Order { id: 7 })
+            .entity_tag(header::EntityTag::new_strong("v7".to_string()))
+            .respond_to(&req);
+
+        assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+yaml"
+        );
+
+        // Without validators on the response there is nothing to evaluate against.
+        let res = Ricksponse::OK(Order { id: 7 }).respond_to(&req);
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_unsafe_method_preconditions_left_to_handler() {
+        // The change was already made when the response is produced, the handler evaluates the
+        // preconditions of state changing requests with `evaluate_preconditions` beforehand.
+        let req = TestRequest::put()
+            .insert_header((header::IF_MATCH, r#""v6""#))
+            .to_http_request();
+        let res = Ricksponse::OK(Order { id: 7 })
+            .entity_tag(header::EntityTag::new_strong("v7".to_string()))
+            .respond_to(&req);
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let req = TestRequest::post()
+            .insert_header((header::IF_NONE_MATCH, "*"))
+            .to_http_request();
+        let res = Ricksponse::CREATED(Order { id: 7 }).respond_to(&req);
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+
+    #[actix_web::test]
+    async fn test_last_modified_not_modified() {
+        let req = TestRequest::default()
+            .insert_header((header::IF_MODIFIED_SINCE, "Mon, 12 Jan 1970 13:46:40 GMT"))
+            .to_http_request();
+
+        let res = Ricksponse::OK(Order { id: 7 })
+            .weak_etag()
+            .header((header::LAST_MODIFIED, "Mon, 12 Jan 1970 13:46:40 GMT"))
+            .respond_to(&req);
+
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert!(res
+            .headers()
+            .get(header::ETAG)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("W/"));
+
+        let res = Ricksponse::<Order>::NOT_FOUND(Order { id: 7 })
+            .header((header::LAST_MODIFIED, "Mon, 12 Jan 1970 13:46:40 GMT"))
+            .respond_to(&req);
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_cache_control() {
+        let req = TestRequest::default().to_http_request();
+
+        let res = Ricksponse::OK(Order { id: 7 })
+            .public()
+            .max_age(30)
+            .private()
+            .max_age(60)
+            .stale_while_revalidate(10)
+            .last_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000))
+            .respond_to(&req);
+
+        assert_eq!(
+            res.headers().get(header::CACHE_CONTROL).unwrap(),
+            "private, max-age=60, stale-while-revalidate=10"
+        );
+        assert_eq!(
+            res.headers().get(header::LAST_MODIFIED).unwrap(),
+            "Mon, 12 Jan 1970 13:46:40 GMT"
+        );
+
+        let res = Ricksponse::OK(Order { id: 7 })
+            .max_age(60)
+            .header((header::CACHE_CONTROL, "no-store"))
+            .respond_to(&req);
+        assert_eq!(
+            res.headers().get(header::CACHE_CONTROL).unwrap(),
+            "no-store"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_vary() {
+        let req = TestRequest::default().to_http_request();
+
+        let res = Ricksponse::OK(Order { id: 7 }).respond_to(&req);
+        assert_eq!(res.headers().get(header::VARY).unwrap(), "accept");
+
+        let res = Ricksponse::OK(Order { id: 7 })
+            .vary(header::ACCEPT_LANGUAGE)
+            .header((header::VARY, "Origin"))
+            .respond_to(&req);
+        assert_eq!(
+            res.headers().get(header::VARY).unwrap(),
+            "accept, Origin, accept-language"
+        );
+
+        let res = Ricksponse::<Order>::from(Err::<Order, _>(OutOfStock)).respond_to(&req);
+        assert_eq!(res.headers().get(header::VARY).unwrap(), "accept");
+    }
 }
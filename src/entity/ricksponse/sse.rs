@@ -0,0 +1,513 @@
+use crate::entity::api_error::{error_response, ApiError};
+use crate::entity::negotiation::{
+    accept_ranges, append_vary, media_types, quality_of, MediaType, NEGOTIATION_HEADERS,
+};
+use crate::entity::payload_control::DefaultControl;
+use crate::entity::payload_error::PayloadError;
+use crate::entity::ricksponse::parts::Parts;
+use crate::entity::ricksponse::ricksponse::Ricksponse;
+use crate::entity::ricksponse_config::RicksponseConfig;
+use crate::error::Error;
+use actix_http::body::{BodyStream, BoxBody};
+use actix_http::header::{HeaderName, TryIntoHeaderPair};
+use actix_web::cookie::Cookie;
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::rt::time::{sleep, Instant, Sleep};
+use actix_web::web::Bytes;
+use actix_web::{dev, FromRequest, HttpRequest, HttpResponse, HttpResponseBuilder, Responder};
+use futures::future::{ready, Ready};
+use futures::{Future, Stream, StreamExt};
+use http::StatusCode;
+use serde::Serialize;
+use simple_serde::{ContentType, SimpleEncoder};
+use std::fmt::{Debug, Formatter, Write};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+const EVENT_STREAM: &str = "text/event-stream";
+
+/// The formats the data of an event can be encoded with, the event stream itself is text.
+const TEXT_CONTENT_TYPES: &[ContentType] = &[
+    ContentType::Json,
+    ContentType::Yaml,
+    ContentType::Toml,
+    ContentType::Json5,
+    ContentType::Ron,
+    ContentType::Lexpr,
+    ContentType::Url,
+];
+
+/// How long the stream stays silent before a keep-alive comment is sent, unless configured.
+const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+type Events<T> = Pin<Box<dyn Stream<Item = Result<Event<T>, Box<dyn ApiError>>>>>;
+
+/// A Server-Sent Event, its `data` is encoded like the body of a `Ricksponse`.
+///
+/// ```rust
+/// use ricksponse::Event;
+/// use std::time::Duration;
+///
+/// let event = Event::new("order shipped".to_string())
+///     .id("42")
+///     .event("shipment")
+///     .retry(Duration::from_secs(5));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event<T> {
+    id: Option<String>,
+    event: Option<String>,
+    retry: Option<Duration>,
+    data: T,
+}
+
+impl<T> Event<T> {
+    pub fn new(data: T) -> Self {
+        Event {
+            id: None,
+            event: None,
+            retry: None,
+            data,
+        }
+    }
+
+    /// The id the client sends back in the `Last-Event-ID` header when it reconnects.
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// The type of the event, clients listen for it with `addEventListener`.
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// How long the client waits before it reconnects once the connection is lost.
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    pub fn data(&self) -> &T {
+        &self.data
+    }
+}
+
+impl<T: Serialize> Event<T> {
+    /// Encodes the event in the wire format of `text/event-stream`.
+    fn encode(&self, content_type: &ContentType) -> Result<Bytes, Error> {
+        let data = self
+            .data
+            .encode(content_type)
+            .map_err(|e| Error::PayloadError(PayloadError::Serialize(e)))?;
+        let data = String::from_utf8_lossy(&data);
+        let data = data.strip_prefix("---\n").unwrap_or(&data);
+
+        let mut buf = String::with_capacity(data.len() + 16);
+        if let Some(id) = &self.id {
+            let _ = writeln!(buf, "id: {}", single_line(id));
+        }
+        if let Some(event) = &self.event {
+            let _ = writeln!(buf, "event: {}", single_line(event));
+        }
+        if let Some(retry) = self.retry {
+            let _ = writeln!(buf, "retry: {}", retry.as_millis());
+        }
+        // Every line of a multi-line format, such as YAML, goes into its own `data` field.
+        let mut lines = data.lines().peekable();
+        if lines.peek().is_none() {
+            buf.push_str("data:\n");
+        }
+        for line in lines {
+            let _ = writeln!(buf, "data: {}", line);
+        }
+        buf.push('\n');
+        Ok(Bytes::from(buf))
+    }
+}
+
+/// Line breaks would end the field early, and a NUL in an id makes clients ignore it.
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n', '\0'], "")
+}
+
+/// A `text/event-stream` response pushing the events of a stream. Built with `Ricksponse::sse`.
+///
+/// The data of the events is encoded as JSON, or in another text format the client lists in its
+/// `Accept` header next to `text/event-stream`, eg. `text/event-stream, application/yaml`. A
+/// comment is sent whenever the stream has been silent for the keep-alive interval, 15 seconds
+/// by default, so proxies do not close the connection.
+///
+/// When an event fails the stream ends, the client reconnects and sends the id of the last event
+/// it received, see `LastEventId`.
+///
+/// ```rust
+/// use futures::stream;
+/// use ricksponse::{Error, Event, LastEventId, Ricksponse, RicksponseSse};
+///
+/// async fn updates(last_event_id: LastEventId) -> RicksponseSse<u32> {
+///     let from = last_event_id.0.and_then(|id| id.parse().ok()).unwrap_or(0);
+///     let events = (from + 1..).map(|n| Ok::<_, Error>(Event::new(n).id(n.to_string())));
+///     Ricksponse::sse(stream::iter(events))
+/// }
+/// ```
+pub struct RicksponseSse<T, C = DefaultControl> {
+    events: Events<T>,
+    keep_alive: Option<Duration>,
+    parts: Parts<C>,
+}
+
+impl<T, C> Ricksponse<T, C> {
+    /// Respond with the events of `stream` as Server-Sent Events, see `RicksponseSse`.
+    ///
+    /// `C` is taken from the declared response type, `Ricksponse::<_>::sse` picks the default
+    /// control where nothing declares it, eg. returning `impl Responder`.
+    pub fn sse<S, E>(stream: S) -> RicksponseSse<T, C>
+    where
+        S: Stream<Item = Result<Event<T>, E>> + 'static,
+        E: ApiError,
+    {
+        RicksponseSse {
+            events: Box::pin(
+                stream.map(|event| event.map_err(|e| Box::new(e) as Box<dyn ApiError>)),
+            ),
+            keep_alive: Some(DEFAULT_KEEP_ALIVE),
+            parts: Parts::default(),
+        }
+    }
+}
+
+impl<T, C> RicksponseSse<T, C> {
+    /// Send a keep-alive comment when no event was sent for `interval`.
+    pub fn keep_alive(mut self, interval: Duration) -> Self {
+        self.keep_alive = Some(interval);
+        self
+    }
+
+    /// Never send keep-alive comments.
+    pub fn without_keep_alive(mut self) -> Self {
+        self.keep_alive = None;
+        self
+    }
+
+    pub fn parts(&self) -> &Parts<C> {
+        &self.parts
+    }
+
+    pub fn parts_mut(&mut self) -> &mut Parts<C> {
+        &mut self.parts
+    }
+
+    /// Insert a header into the response, see `Ricksponse::header`.
+    pub fn header(mut self, header: impl TryIntoHeaderPair) -> Self {
+        self.parts.insert_header(header);
+        self
+    }
+
+    /// Add a cookie to the response.
+    pub fn cookie(mut self, cookie: Cookie<'static>) -> Self {
+        self.parts.add_cookie(cookie);
+        self
+    }
+
+    /// Add a request header the response varies on, see `Ricksponse::vary`.
+    pub fn vary(mut self, name: HeaderName) -> Self {
+        self.parts.add_vary(name);
+        self
+    }
+}
+
+impl<T, C> Debug for RicksponseSse<T, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RicksponseSse")
+            .field("keep_alive", &self.keep_alive)
+            .field("parts", &self.parts)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Serialize + 'static, C> Responder for RicksponseSse<T, C> {
+    type Body = BoxBody;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+        let config = RicksponseConfig::from_req(req);
+        let mut res = match negotiate(req, config.allowed_content_types()) {
+            Some(content_type) => HttpResponseBuilder::new(StatusCode::OK)
+                .content_type(EVENT_STREAM)
+                .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+                .body(BodyStream::new(EventStream {
+                    events: self.events,
+                    content_type,
+                    keep_alive: self
+                        .keep_alive
+                        .map(|interval| (interval, Box::pin(sleep(interval)))),
+                    done: false,
+                })),
+            None => error_response(&Error::NoAcceptableContentType, req),
+        };
+        append_vary(
+            res.headers_mut(),
+            NEGOTIATION_HEADERS.iter().map(HeaderName::as_str),
+        );
+        self.parts.finish(res, req)
+    }
+}
+
+/// Picks the format of the event data, or `None` when the client does not accept an event
+/// stream or the `RicksponseConfig` allows no text format to send the event data in.
+///
+/// Only media ranges naming a format explicitly are considered, so a plain
+/// `Accept: text/event-stream` as sent by browsers gets the first text format the
+/// `RicksponseConfig` allows.
+fn negotiate(req: &HttpRequest, allowed: &[ContentType]) -> Option<&'static ContentType> {
+    let ranges = accept_ranges(req.headers());
+    let event_stream = MediaType::from_str(EVENT_STREAM).ok()?;
+    if !ranges.is_empty() && quality_of(&ranges, &event_stream).unwrap_or(0) == 0 {
+        return None;
+    }
+    let candidates: Vec<&'static ContentType> = allowed
+        .iter()
+        .filter_map(|c| TEXT_CONTENT_TYPES.iter().find(|text| *text == c))
+        .collect();
+    let explicit: Vec<_> = ranges
+        .into_iter()
+        .filter(|range| !range.is_wildcard() && range.media_type() != &event_stream)
+        .collect();
+    let mut best: Option<(u16, &'static ContentType)> = None;
+    for content_type in &candidates {
+        let quality = media_types(content_type)
+            .iter()
+            .filter_map(|m| MediaType::from_str(m).ok())
+            .filter_map(|m| quality_of(&explicit, &m))
+            .max()
+            .unwrap_or(0);
+        if quality > 0 && best.is_none_or(|(q, _)| quality > q) {
+            best = Some((quality, content_type));
+        }
+    }
+    best.map(|(_, content_type)| content_type)
+        .or_else(|| candidates.first().copied())
+}
+
+/// The events of a `RicksponseSse` encoded into chunks of the body, with keep-alive comments in
+/// between.
+struct EventStream<T> {
+    events: Events<T>,
+    content_type: &'static ContentType,
+    keep_alive: Option<(Duration, Pin<Box<Sleep>>)>,
+    done: bool,
+}
+
+impl<T: Serialize> Stream for EventStream<T> {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        let chunk = match this.events.as_mut().poll_next(cx) {
+            Poll::Ready(Some(Ok(event))) => event.encode(this.content_type),
+            Poll::Ready(Some(Err(e))) => Err(Error::StreamFailed(e)),
+            Poll::Ready(None) => {
+                this.done = true;
+                return Poll::Ready(None);
+            }
+            Poll::Pending => {
+                let Some((_, timer)) = &mut this.keep_alive else {
+                    return Poll::Pending;
+                };
+                if timer.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                Ok(Bytes::from_static(b": keep-alive\n\n"))
+            }
+        };
+        if let Some((interval, timer)) = &mut this.keep_alive {
+            timer.as_mut().reset(Instant::now() + *interval);
+        }
+        this.done = chunk.is_err();
+        Poll::Ready(Some(chunk))
+    }
+}
+
+/// The `Last-Event-ID` header a reconnecting client sends, the id of the last event it received.
+///
+/// `None` when the client connects for the first time.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LastEventId(pub Option<String>);
+
+impl FromRequest for LastEventId {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut dev::Payload) -> Self::Future {
+        let id = req
+            .headers()
+            .get("last-event-id")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        ready(Ok(LastEventId(id)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Error, Event, LastEventId, Ricksponse, RicksponseConfig, Validated};
+    use actix_web::cookie::Cookie;
+    use actix_web::rt::time::sleep;
+    use actix_web::test::TestRequest;
+    use actix_web::{body, http::header, FromRequest, Responder};
+    use futures::stream;
+    use http::StatusCode;
+    use simple_serde::ContentType;
+    use std::time::Duration;
+
+    #[derive(Serialize, Debug)]
+    struct Shipment {
+        order: u32,
+        carrier: &'static str,
+    }
+
+    fn shipments() -> impl futures::Stream<Item = Result<Event<Shipment>, Error>> {
+        stream::iter(vec![
+            Ok(Event::new(Shipment {
+                order: 7,
+                carrier: "DHL",
+            })
+            .id("1")
+            .event("shipment")
+            .retry(Duration::from_secs(3))),
+            Ok(Event::new(Shipment {
+                order: 8,
+                carrier: "UPS",
+            })),
+        ])
+    }
+
+    async fn body_for(accept: &str) -> String {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, accept))
+            .to_http_request();
+        let res = Ricksponse::<_>::sse(shipments()).respond_to(&req);
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/event-stream"
+        );
+        assert_eq!(
+            res.headers().get(header::CACHE_CONTROL).unwrap(),
+            "no-cache"
+        );
+        let body = body::to_bytes(res.into_body()).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_sse_json_events() {
+        assert_eq!(
+            body_for("text/event-stream").await,
+            "id: 1\nevent: shipment\nretry: 3000\ndata: {\"order\":7,\"carrier\":\"DHL\"}\n\n\
+             data: {\"order\":8,\"carrier\":\"UPS\"}\n\n"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_sse_negotiated_yaml_events() {
+        assert_eq!(
+            body_for("text/event-stream, application/yaml").await,
+            "id: 1\nevent: shipment\nretry: 3000\ndata: order: 7\ndata: carrier: DHL\n\n\
+             data: order: 8\ndata: carrier: UPS\n\n"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_sse_not_acceptable() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/json"))
+            .to_http_request();
+
+        let res = Ricksponse::<_>::sse(shipments()).respond_to(&req);
+
+        assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+    }
+
+    #[actix_web::test]
+    async fn test_sse_config_content_type() {
+        let req = TestRequest::default()
+            .app_data(
+                RicksponseConfig::default()
+                    .content_types(vec![ContentType::Cbor, ContentType::Toml]),
+            )
+            .to_http_request();
+        let events = stream::iter(vec![Ok::<_, Error>(Event::new(Shipment {
+            order: 7,
+            carrier: "DHL",
+        }))]);
+
+        let res = Ricksponse::<_>::sse(events).respond_to(&req);
+        let body = body::to_bytes(res.into_body()).await.unwrap();
+
+        assert_eq!(body, "data: order = 7\ndata: carrier = \"DHL\"\n\n");
+
+        let req = TestRequest::default()
+            .app_data(RicksponseConfig::default().content_types(vec![ContentType::Cbor]))
+            .to_http_request();
+        let res = Ricksponse::<_>::sse(shipments()).respond_to(&req);
+        assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+    }
+
+    #[actix_web::test]
+    async fn test_sse_cookie_and_vary() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "text/event-stream"))
+            .to_http_request();
+        let res = Ricksponse::<Shipment, Validated>::sse(shipments())
+            .cookie(Cookie::new("session", "abc"))
+            .vary(header::ACCEPT_LANGUAGE)
+            .respond_to(&req);
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get(header::SET_COOKIE).unwrap(),
+            "session=abc"
+        );
+        assert_eq!(
+            res.headers().get(header::VARY).unwrap(),
+            "accept, accept-language"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_sse_keep_alive() {
+        let req = TestRequest::default().to_http_request();
+        let events = stream::once(async {
+            sleep(Duration::from_millis(100)).await;
+            Ok::<_, Error>(Event::new(1).id("a\nb"))
+        });
+
+        let res = Ricksponse::<_>::sse(events)
+            .keep_alive(Duration::from_millis(30))
+            .respond_to(&req);
+        let body = body::to_bytes(res.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+
+        assert!(body.starts_with(": keep-alive\n\n"), "{}", body);
+        assert!(body.ends_with("id: ab\ndata: 1\n\n"), "{}", body);
+    }
+
+    #[actix_web::test]
+    async fn test_last_event_id() {
+        let req = TestRequest::default()
+            .insert_header(("Last-Event-ID", "41"))
+            .to_http_request();
+        let id = LastEventId::extract(&req).await.unwrap();
+        assert_eq!(id, LastEventId(Some("41".to_string())));
+
+        let req = TestRequest::default().to_http_request();
+        let id = LastEventId::extract(&req).await.unwrap();
+        assert_eq!(id, LastEventId(None));
+    }
+}
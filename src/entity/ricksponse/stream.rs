@@ -0,0 +1,652 @@
+use crate::entity::api_error::{error_response, ApiError};
+use crate::entity::negotiation::{
+    accept_ranges, append_vary, quality_of, MediaType, NEGOTIATION_HEADERS,
+};
+use crate::entity::payload_error::PayloadError;
+use crate::entity::payload_stream::PayloadStream;
+use crate::entity::ricksponse::parts::Parts;
+use crate::entity::ricksponse::ricksponse::Ricksponse;
+use crate::entity::ricksponse_config::RicksponseConfig;
+use crate::error::Error;
+use actix_http::body::{BodyStream, BoxBody};
+use actix_http::header::{HeaderName, TryIntoHeaderPair};
+use actix_web::cookie::Cookie;
+use actix_web::web::Bytes;
+use actix_web::{dev, FromRequest, HttpRequest, HttpResponse, HttpResponseBuilder, Responder};
+use futures::future::{self, Ready};
+use futures::{ready, stream, Stream};
+use http::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use simple_serde::{ContentType, SimpleEncoder};
+use std::fmt::{Debug, Formatter};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+
+type Items<T, E> = Pin<Box<dyn Stream<Item = Result<T, E>>>>;
+
+/// A stream of items, decoded from a request body or encoded into a response body while the items
+/// arrive.
+///
+/// ## Responder
+///
+/// Built with `Ricksponse::stream`, the body is encoded item by item while the items are
+/// produced, eg. rows from a database cursor. The format is negotiated from the `Accept` header like the one of a `Ricksponse`:
+///
+/// | Media type             | Body                                          |
+/// |------------------------|-----------------------------------------------|
+/// | `application/json`     | A JSON array, the default                     |
+/// | `application/x-ndjson` | One JSON text per line                        |
+/// | `application/json-seq` | JSON text sequences, RFC 7464                 |
+/// | `application/cbor-seq` | CBOR sequences, RFC 8742                      |
+/// | `application/yaml`     | One YAML document per item                    |
+///
+/// The status and headers are sent before the first item, so an item that fails, or fails to
+/// encode, can no longer be answered with problem details. The body is cut off instead and the
+/// client sees an incomplete response.
+///
+/// ```rust
+/// use futures::stream;
+/// use ricksponse::{Error, Ricksponse, RicksponseStream};
+///
+/// async fn export() -> RicksponseStream<u32, Error> {
+///     Ricksponse::stream(stream::iter((0..1000).map(Ok::<_, Error>)))
+/// }
+/// ```
+///
+/// ## Extractor
+///
+/// Request bodies sent as `application/x-ndjson`, `application/json-seq` or
+/// `application/cbor-seq` are decoded record by record, so bulk imports are not held in memory
+/// as a whole. Each record is limited by `RicksponseConfig::limit` and the whole body by
+/// `RicksponseConfig::stream_limit`.
+///
+/// A body that can not be read at all, eg. for its content type, yields a single error, unless
+/// the `RicksponseConfig::error_handler` rejects the request. A record that fails to decode
+/// yields an error and the stream goes on with the next record. Streamed bodies can not be sent
+/// with a `Content-Encoding`, such a body is rejected with `415 Unsupported Media Type`.
+///
+/// ```rust
+/// use futures::StreamExt;
+/// use ricksponse::{Ricksponse, RicksponseStream};
+/// use serde_derive::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Order {
+///     id: u32,
+/// }
+///
+/// async fn import(mut orders: RicksponseStream<Order>) -> Ricksponse<usize> {
+///     let mut imported = 0;
+///     while let Some(order) = orders.next().await {
+///         match order {
+///             Ok(_order) => imported += 1,
+///             Err(e) => return Ricksponse::from(Err::<usize, _>(e)),
+///         }
+///     }
+///     Ricksponse::OK(imported)
+/// }
+/// ```
+pub struct RicksponseStream<T, E = PayloadError> {
+    items: Items<T, E>,
+    http_code: Option<u16>,
+    parts: Parts,
+}
+
+impl<T> Ricksponse<T> {
+    /// Respond with the items of `stream`, see `RicksponseStream`.
+    pub fn stream<S, E>(stream: S) -> RicksponseStream<T, E>
+    where
+        S: Stream<Item = Result<T, E>> + 'static,
+        E: ApiError,
+    {
+        RicksponseStream {
+            items: Box::pin(stream),
+            http_code: None,
+            parts: Parts::default(),
+        }
+    }
+}
+
+impl<T, E> RicksponseStream<T, E> {
+    /// Respond with `code` instead of `200 OK`.
+    pub fn http_code(mut self, code: u16) -> Self {
+        self.http_code = Some(code);
+        self
+    }
+
+    pub fn parts(&self) -> &Parts {
+        &self.parts
+    }
+
+    pub fn parts_mut(&mut self) -> &mut Parts {
+        &mut self.parts
+    }
+
+    /// Insert a header into the response, see `Ricksponse::header`.
+    pub fn header(mut self, header: impl TryIntoHeaderPair) -> Self {
+        self.parts.insert_header(header);
+        self
+    }
+
+    /// Add a cookie to the response.
+    pub fn cookie(mut self, cookie: Cookie<'static>) -> Self {
+        self.parts.add_cookie(cookie);
+        self
+    }
+
+    /// Add a request header the response varies on, see `Ricksponse::vary`.
+    pub fn vary(mut self, name: HeaderName) -> Self {
+        self.parts.add_vary(name);
+        self
+    }
+}
+
+impl<T, E> Debug for RicksponseStream<T, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RicksponseStream")
+            .field("http_code", &self.http_code)
+            .field("parts", &self.parts)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Serialize + 'static, E: ApiError> Responder for RicksponseStream<T, E> {
+    type Body = BoxBody;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+        let config = RicksponseConfig::from_req(req);
+        let mut res = match StreamFormat::negotiate(req, config.allowed_content_types()) {
+            Some(format) => {
+                let status = self
+                    .http_code
+                    .and_then(|code| StatusCode::from_u16(code).ok())
+                    .unwrap_or(StatusCode::OK);
+                HttpResponseBuilder::new(status)
+                    .content_type(format.media_type())
+                    .body(BodyStream::new(EncodedStream {
+                        items: self.items,
+                        format,
+                        first: true,
+                        done: false,
+                    }))
+            }
+            None => error_response(&Error::NoAcceptableContentType, req),
+        };
+        append_vary(
+            res.headers_mut(),
+            NEGOTIATION_HEADERS.iter().map(HeaderName::as_str),
+        );
+        self.parts.finish(res, req)
+    }
+}
+
+impl<T, E> Stream for RicksponseStream<T, E> {
+    type Item = Result<T, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.items.as_mut().poll_next(cx)
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for RicksponseStream<T> {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut dev::Payload) -> Self::Future {
+        let mut parts = Parts::default();
+        let items: Items<T, PayloadError> = match PayloadStream::new(req, payload) {
+            Ok(items) => {
+                parts.media_type = Some(items.media_type().clone());
+                Box::pin(items)
+            }
+            Err(e) => match RicksponseConfig::from_req(req).handle_error(e, req) {
+                Ok(rejection) => return future::err(rejection),
+                Err(e) => Box::pin(stream::once(future::err(e))),
+            },
+        };
+        future::ok(RicksponseStream {
+            items,
+            http_code: None,
+            parts,
+        })
+    }
+}
+
+/// The formats items can be streamed in, in order of server preference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamFormat {
+    JsonArray,
+    NdJson,
+    JsonSeq,
+    CborSeq,
+    Yaml,
+}
+
+impl StreamFormat {
+    const ALL: [StreamFormat; 5] = [
+        StreamFormat::JsonArray,
+        StreamFormat::NdJson,
+        StreamFormat::JsonSeq,
+        StreamFormat::CborSeq,
+        StreamFormat::Yaml,
+    ];
+
+    fn media_type(self) -> &'static str {
+        match self {
+            StreamFormat::JsonArray => "application/json",
+            StreamFormat::NdJson => "application/x-ndjson",
+            StreamFormat::JsonSeq => "application/json-seq",
+            StreamFormat::CborSeq => "application/cbor-seq",
+            StreamFormat::Yaml => "application/yaml",
+        }
+    }
+
+    /// The format a single item is encoded with.
+    fn content_type(self) -> ContentType {
+        match self {
+            StreamFormat::CborSeq => ContentType::Cbor,
+            StreamFormat::Yaml => ContentType::Yaml,
+            _ => ContentType::Json,
+        }
+    }
+
+    /// Picks the most preferred format the client accepts and the `RicksponseConfig` allows.
+    ///
+    /// Ties are broken by the order of the content types in the configuration, so
+    /// `default_content_type` picks the format a client without `Accept` header gets.
+    fn negotiate(req: &HttpRequest, allowed: &[ContentType]) -> Option<StreamFormat> {
+        let ranges = accept_ranges(req.headers());
+        let mut candidates = allowed.iter().flat_map(|content_type| {
+            Self::ALL
+                .into_iter()
+                .filter(move |format| format.content_type() == *content_type)
+        });
+        if ranges.is_empty() {
+            return candidates.next();
+        }
+        let mut best: Option<(u16, StreamFormat)> = None;
+        for format in candidates {
+            let media_type = MediaType::from_str(format.media_type()).ok()?;
+            let quality = quality_of(&ranges, &media_type).unwrap_or(0);
+            if quality > 0 && best.is_none_or(|(q, _)| quality > q) {
+                best = Some((quality, format));
+            }
+        }
+        best.map(|(_, format)| format)
+    }
+
+    /// Writes what goes in front of an item.
+    fn prefix(self, first: bool, buf: &mut Vec<u8>) {
+        match self {
+            StreamFormat::JsonArray => buf.push(if first { b'[' } else { b',' }),
+            StreamFormat::JsonSeq => buf.push(0x1e),
+            StreamFormat::Yaml => buf.extend_from_slice(b"---\n"),
+            StreamFormat::NdJson | StreamFormat::CborSeq => {}
+        }
+    }
+
+    /// Writes what goes after an item.
+    fn suffix(self, buf: &mut Vec<u8>) {
+        match self {
+            StreamFormat::NdJson | StreamFormat::JsonSeq => buf.push(b'\n'),
+            StreamFormat::Yaml if buf.last() != Some(&b'\n') => buf.push(b'\n'),
+            _ => {}
+        }
+    }
+
+    /// What closes the body after the last item.
+    fn end(self, first: bool) -> Option<&'static [u8]> {
+        match self {
+            StreamFormat::JsonArray if first => Some(b"[]"),
+            StreamFormat::JsonArray => Some(b"]"),
+            _ => None,
+        }
+    }
+}
+
+/// The items of a `RicksponseStream` encoded into chunks of the body.
+struct EncodedStream<T, E> {
+    items: Items<T, E>,
+    format: StreamFormat,
+    first: bool,
+    done: bool,
+}
+
+impl<T: Serialize, E> EncodedStream<T, E> {
+    fn encode(&self, item: &T) -> Result<Bytes, Error> {
+        let encoded = item
+            .encode(self.format.content_type())
+            .map_err(|e| Error::PayloadError(PayloadError::Serialize(e)))?;
+        let mut encoded: &[u8] = &encoded;
+        if self.format == StreamFormat::Yaml {
+            // Every document gets its own marker, whether the encoder writes one or not.
+            encoded = encoded.strip_prefix(b"---\n").unwrap_or(encoded);
+        }
+        let mut buf = Vec::with_capacity(encoded.len() + 2);
+        self.format.prefix(self.first, &mut buf);
+        buf.extend_from_slice(encoded);
+        self.format.suffix(&mut buf);
+        Ok(Bytes::from(buf))
+    }
+}
+
+impl<T: Serialize, E: ApiError> Stream for EncodedStream<T, E> {
+    type Item = Result<Bytes, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        let chunk = match ready!(this.items.as_mut().poll_next(cx)) {
+            Some(Ok(item)) => {
+                let chunk = this.encode(&item);
+                this.first = false;
+                chunk
+            }
+            Some(Err(e)) => Err(Error::StreamFailed(Box::new(e))),
+            None => {
+                this.done = true;
+                return Poll::Ready(this.format.end(this.first).map(|end| Ok(end.into())));
+            }
+        };
+        // The body can not recover once an item is missing from it.
+        this.done = chunk.is_err();
+        Poll::Ready(Some(chunk))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ApiError, Error, PayloadError, Ricksponse, RicksponseConfig, RicksponseStream};
+    use actix_web::test::TestRequest;
+    use actix_web::{body, dev, http::header, FromRequest, Responder};
+    use futures::{stream, StreamExt};
+    use http::StatusCode;
+    use simple_serde::ContentType;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Order {
+        id: u32,
+    }
+
+    #[derive(Debug)]
+    struct CursorClosed;
+
+    impl ApiError for CursorClosed {}
+
+    fn orders(ids: &[u32]) -> impl futures::Stream<Item = Result<Order, Error>> {
+        let orders: Vec<_> = ids.iter().map(|id| Ok(Order { id: *id })).collect();
+        stream::iter(orders)
+    }
+
+    async fn body_for(accept: &str, ids: &[u32]) -> (String, String) {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, accept))
+            .to_http_request();
+        let res = Ricksponse::stream(orders(ids)).respond_to(&req);
+        assert_eq!(res.status(), StatusCode::OK);
+        let content_type = res.headers().get(header::CONTENT_TYPE).unwrap().clone();
+        let body = body::to_bytes(res.into_body()).await.unwrap();
+        (
+            content_type.to_str().unwrap().to_string(),
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[actix_web::test]
+    async fn test_stream_formats() {
+        for (accept, ids, content_type, expected) in [
+            (
+                "*/*",
+                &[1, 2][..],
+                "application/json",
+                r#"[{"id":1},{"id":2}]"#,
+            ),
+            ("application/json", &[], "application/json", "[]"),
+            (
+                "application/x-ndjson",
+                &[1, 2],
+                "application/x-ndjson",
+                "{\"id\":1}\n{\"id\":2}\n",
+            ),
+            (
+                "application/json-seq",
+                &[1],
+                "application/json-seq",
+                "\u{1e}{\"id\":1}\n",
+            ),
+            (
+                "application/yaml",
+                &[1, 2],
+                "application/yaml",
+                "---\nid: 1\n---\nid: 2\n",
+            ),
+        ] {
+            assert_eq!(
+                body_for(accept, ids).await,
+                (content_type.to_string(), expected.to_string()),
+                "{}",
+                accept
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_stream_cbor_sequence() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/cbor-seq"))
+            .to_http_request();
+        let res = Ricksponse::stream(orders(&[1, 2])).respond_to(&req);
+        let body = body::to_bytes(res.into_body()).await.unwrap();
+
+        // Two maps of one entry, `id: 1` and `id: 2`.
+        assert_eq!(body.as_ref(), b"\xa1\x62id\x01\xa1\x62id\x02".as_ref());
+    }
+
+    #[actix_web::test]
+    async fn test_stream_not_acceptable() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/yaml"))
+            .app_data(RicksponseConfig::default().content_types(vec![ContentType::Json]))
+            .to_http_request();
+
+        let res = Ricksponse::stream(orders(&[1])).respond_to(&req);
+
+        assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+        assert_eq!(res.headers().get(header::VARY).unwrap(), "accept");
+    }
+
+    #[actix_web::test]
+    async fn test_stream_default_content_type() {
+        let req = TestRequest::default()
+            .app_data(RicksponseConfig::default().default_content_type(ContentType::Yaml))
+            .to_http_request();
+
+        let res = Ricksponse::stream(orders(&[1])).respond_to(&req);
+
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/yaml"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_stream_cut_off_on_error() {
+        let req = TestRequest::default().to_http_request();
+        let items = stream::iter(vec![Ok(Order { id: 1 }), Err(CursorClosed)]);
+
+        let res = Ricksponse::stream(items)
+            .http_code(206)
+            .header(("x-export", "orders"))
+            .respond_to(&req);
+
+        assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(res.headers().get("x-export").unwrap(), "orders");
+        assert!(body::to_bytes(res.into_body()).await.is_err());
+    }
+
+    async fn extract(
+        req: TestRequest,
+        chunks: &[&'static [u8]],
+    ) -> Result<Vec<Result<Order, PayloadError>>, actix_web::Error> {
+        let (mut sender, payload) = actix_http::h1::Payload::create(false);
+        for chunk in chunks {
+            sender.feed_data(chunk.to_vec().into());
+        }
+        sender.feed_eof();
+        let req = req.to_http_request();
+        let orders =
+            RicksponseStream::<Order>::from_request(&req, &mut dev::Payload::from(payload));
+        Ok(orders.await?.collect().await)
+    }
+
+    fn ids(items: &[Result<Order, PayloadError>]) -> Vec<Option<u32>> {
+        items
+            .iter()
+            .map(|item| item.as_ref().ok().map(|order| order.id))
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn test_extract_ndjson() {
+        let req =
+            TestRequest::default().insert_header((header::CONTENT_TYPE, "application/x-ndjson"));
+
+        let items = extract(req, &[b"{\"id\":1}\n\n{\"id\":", b"\"two\"}\r\n{\"id\":3}"])
+            .await
+            .unwrap();
+
+        assert_eq!(ids(&items), [Some(1), None, Some(3)]);
+        assert!(matches!(items[1], Err(PayloadError::Deserialize(_))));
+    }
+
+    #[actix_web::test]
+    async fn test_extract_json_seq() {
+        let req =
+            TestRequest::default().insert_header((header::CONTENT_TYPE, "application/json-seq"));
+
+        let items = extract(req, &[b"\x1e{\n  \"id\": 1\n}\n\x1e{\"id\"", b":2}\n"])
+            .await
+            .unwrap();
+
+        assert_eq!(ids(&items), [Some(1), Some(2)]);
+    }
+
+    #[actix_web::test]
+    async fn test_extract_cbor_seq() {
+        let req =
+            TestRequest::default().insert_header((header::CONTENT_TYPE, "application/cbor-seq"));
+
+        let items = extract(req, &[b"\xa1\x62id", b"\x01\xa1\x62id\x02"])
+            .await
+            .unwrap();
+        assert_eq!(ids(&items), [Some(1), Some(2)]);
+
+        let req =
+            TestRequest::default().insert_header((header::CONTENT_TYPE, "application/cbor-seq"));
+        let items = extract(req, &[b"\xa1\x62id\x01\xa1\x62"]).await.unwrap();
+        assert_eq!(ids(&items), [Some(1), None]);
+    }
+
+    #[actix_web::test]
+    async fn test_extract_byte_by_byte() {
+        let cbor: &'static [u8] =
+            b"\xa3\x62id\x01\x61x\x9f\x01\x42\x01\x02\xa1\x61a\xf9\x3e\x00\xff\
+            \x61t\xc1\x19\x03\xe8\xa1\x62id\x02";
+        for (content_type, body) in [
+            ("application/x-ndjson", &b"{\"id\":1}\n{\"id\":2}\n"[..]),
+            (
+                "application/json-seq",
+                &b"\x1e{\"id\":1}\n\x1e{\"id\":2}\n"[..],
+            ),
+            ("application/cbor-seq", cbor),
+        ] {
+            let req = TestRequest::default().insert_header((header::CONTENT_TYPE, content_type));
+            let chunks = body.chunks(1).collect::<Vec<_>>();
+            let items = extract(req, &chunks).await.unwrap();
+            assert_eq!(ids(&items), [Some(1), Some(2)], "{}", content_type);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_extract_content_encoding() {
+        let req = TestRequest::default()
+            .insert_header((header::CONTENT_TYPE, "application/x-ndjson"))
+            .insert_header((header::CONTENT_ENCODING, "gzip"));
+        let items = extract(req, &[b"\x1f\x8b"]).await.unwrap();
+        assert!(matches!(&items[..], [Err(PayloadError::ContentEncoding(e))] if e == "gzip"));
+    }
+
+    #[actix_web::test]
+    async fn test_extract_limits() {
+        let req = TestRequest::default()
+            .insert_header((header::CONTENT_TYPE, "application/x-ndjson"))
+            .app_data(RicksponseConfig::default().limit(10));
+        let items = extract(req, &[b"{\"id\":1}\n{\"id\":100000}\n{\"id\":3}\n"])
+            .await
+            .unwrap();
+        assert_eq!(ids(&items), [Some(1), None, Some(3)]);
+        assert!(matches!(
+            items[1],
+            Err(PayloadError::OverflowKnownLength { limit: 10, .. })
+        ));
+
+        let req = TestRequest::default()
+            .insert_header((header::CONTENT_TYPE, "application/x-ndjson"))
+            .app_data(RicksponseConfig::default().limit(10));
+        let items = extract(req, &[b"{\"id\":1}\n{\"id\":", b"100000"])
+            .await
+            .unwrap();
+        assert_eq!(ids(&items), [Some(1), None]);
+        assert!(matches!(
+            items[1],
+            Err(PayloadError::Overflow { limit: 10 })
+        ));
+
+        let req = TestRequest::default()
+            .insert_header((header::CONTENT_TYPE, "application/x-ndjson"))
+            .app_data(RicksponseConfig::default().stream_limit(20));
+        let items = extract(req, &[b"{\"id\":1}\n", b"{\"id\":2}\n", b"{\"id\":3}\n"])
+            .await
+            .unwrap();
+        assert_eq!(ids(&items), [Some(1), Some(2), None]);
+
+        let req = TestRequest::default()
+            .insert_header((header::CONTENT_TYPE, "application/x-ndjson"))
+            .insert_header((header::CONTENT_LENGTH, "17"))
+            .app_data(RicksponseConfig::default().stream_limit(16));
+        let items = extract(req, &[]).await.unwrap();
+        assert!(matches!(
+            items[..],
+            [Err(PayloadError::OverflowKnownLength {
+                length: 17,
+                limit: 16
+            })]
+        ));
+    }
+
+    #[actix_web::test]
+    async fn test_extract_content_type() {
+        let req = TestRequest::default().insert_header((header::CONTENT_TYPE, "application/json"));
+        let items = extract(req, &[b"[]"]).await.unwrap();
+        assert!(matches!(items[..], [Err(PayloadError::ContentType { .. })]));
+
+        let req = TestRequest::default()
+            .insert_header((header::CONTENT_TYPE, "application/cbor-seq"))
+            .app_data(
+                RicksponseConfig::default()
+                    .content_types(vec![ContentType::Json])
+                    .error_handler(crate::negotiated_error),
+            );
+        let rejection = extract(req, &[]).await.unwrap_err();
+        let res = rejection.error_response();
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(
+            res.headers().get("accept-post").unwrap(),
+            "application/x-ndjson, application/json-seq"
+        );
+    }
+}
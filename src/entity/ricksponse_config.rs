@@ -0,0 +1,381 @@
+use crate::entity::api_error::negotiated_error;
+use crate::entity::envelope::Envelope;
+use crate::entity::negotiation::SUPPORTED_CONTENT_TYPES;
+use crate::entity::payload_error::PayloadError;
+use actix_web::{web, HttpRequest};
+use simple_serde::ContentType;
+use std::sync::Arc;
+
+pub(crate) const DEFAULT_LIMIT: usize = 41_943_040; // 40 mb
+pub(crate) const DEFAULT_BUFFER_CAPACITY: usize = 8192;
+pub(crate) const DEFAULT_STREAM_LIMIT: usize = 1_073_741_824; // 1 gb
+pub(crate) const DEFAULT_COMPRESSION_RATIO: usize = 100;
+
+type ErrorHandler = Arc<dyn Fn(PayloadError, &HttpRequest) -> actix_web::Error + Send + Sync>;
+
+/// `Ricksponse` and `Hateoas` extractor and responder configuration.
+///
+/// The configuration is looked up in the app data of the request, so it can be set for the whole
+/// app, a scope or a single resource. Both `RicksponseConfig` and `web::Data<RicksponseConfig>`
+/// are recognized, in that order. The `PayloadControl` constants of the extracted type, when
+/// set, take precedence over the limits configured here.
+///
+/// ## Example
+///
+/// ```rust
+/// use actix_web::{error, web, App, HttpResponse};
+/// use ricksponse::RicksponseConfig;
+/// use simple_serde::ContentType;
+///
+/// let config = RicksponseConfig::default()
+///     // limit request payload size
+///     .limit(4096)
+///     // only speak json and yaml
+///     .content_types(vec![ContentType::Json, ContentType::Yaml])
+///     // answer in yaml when the client has no preference
+///     .default_content_type(ContentType::Yaml)
+///     // use custom error handler
+///     .error_handler(|err, _req| {
+///         error::InternalError::from_response(err, HttpResponse::Conflict().into()).into()
+///     });
+///
+/// let app = App::new().service(web::scope("/api").app_data(config));
+/// ```
+#[derive(Clone)]
+pub struct RicksponseConfig {
+    pub(crate) limit: usize,
+    pub(crate) stream_limit: usize,
+    pub(crate) buffer_capacity: usize,
+    pub(crate) decompressed_limit: usize,
+    pub(crate) compression_ratio: usize,
+    content_types: Option<Arc<Vec<ContentType>>>,
+    err_handler: Option<ErrorHandler>,
+    envelope: Option<Envelope>,
+}
+
+impl RicksponseConfig {
+    /// Set maximum accepted payload size. The default limit is 40MB.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Set maximum accepted size of a streamed payload, see `RicksponseStream`. Each item of the
+    /// stream is limited by `limit`. The default limit is 1GB.
+    pub fn stream_limit(mut self, limit: usize) -> Self {
+        self.stream_limit = limit;
+        self
+    }
+
+    /// Set maximum size of a request payload after decompressing its `Content-Encoding`, `limit`
+    /// applies to the compressed size. The default limit is 40MB.
+    pub fn decompressed_limit(mut self, limit: usize) -> Self {
+        self.decompressed_limit = limit;
+        self
+    }
+
+    /// Set how many times its compressed size a request payload may decompress to. Payloads
+    /// decompressing to less than 64KB are not checked. Defaults to 100.
+    pub fn compression_ratio_limit(mut self, ratio: usize) -> Self {
+        self.compression_ratio = ratio;
+        self
+    }
+
+    /// Set the initial capacity of the buffer the payload is read into. Defaults to 8KB.
+    pub fn buffer_capacity(mut self, capacity: usize) -> Self {
+        self.buffer_capacity = capacity;
+        self
+    }
+
+    /// Set the content types accepted in request bodies and produced in responses.
+    ///
+    /// A body in any other content type is rejected with `PayloadError::ContentType`, answered
+    /// with `415 Unsupported Media Type` and the accepted media types in an `Accept-Post` header,
+    /// `Accept-Patch` for a `PATCH` request. All content types are accepted by default.
+    ///
+    /// The order is the server preference used when the client ranks formats equally, the first
+    /// content type is therefore the default response format.
+    pub fn content_types(mut self, content_types: Vec<ContentType>) -> Self {
+        self.content_types = Some(Arc::new(content_types));
+        self
+    }
+
+    /// Set the content type to respond with when the client has no preference.
+    ///
+    /// The content type is moved to the front of the allowed content types, and added to them
+    /// if it was not allowed yet.
+    pub fn default_content_type(mut self, content_type: ContentType) -> Self {
+        let mut content_types = self
+            .allowed_content_types()
+            .iter()
+            .filter(|c| **c != content_type)
+            .filter_map(copy_content_type)
+            .collect::<Vec<ContentType>>();
+        content_types.insert(0, content_type);
+        self.content_types = Some(Arc::new(content_types));
+        self
+    }
+
+    /// Set custom error handler.
+    ///
+    /// When set, a payload that fails to extract rejects the request with the returned error
+    /// instead of handing a `Ricksponse::Error` to the handler.
+    /// `negotiated_error` rejects with problem details encoded in the format negotiated with the
+    /// client.
+    pub fn error_handler<F>(mut self, f: F) -> Self
+    where
+        F: Fn(PayloadError, &HttpRequest) -> actix_web::Error + Send + Sync + 'static,
+    {
+        self.err_handler = Some(Arc::new(f));
+        self
+    }
+
+    /// Reject a request whose payload fails to extract with problem details encoded in the format
+    /// negotiated with the client, the handler is not called. Shorthand for
+    /// `error_handler(negotiated_error)`.
+    pub fn reject_on_error(self) -> Self {
+        self.error_handler(negotiated_error)
+    }
+
+    /// Wrap the data of every response in an envelope, see `Envelope`.
+    pub fn envelope(mut self, envelope: Envelope) -> Self {
+        self.envelope = Some(envelope);
+        self
+    }
+
+    pub(crate) fn response_envelope(&self) -> Option<&Envelope> {
+        self.envelope.as_ref()
+    }
+
+    /// The allowed content types in order of preference.
+    pub(crate) fn allowed_content_types(&self) -> &[ContentType] {
+        self.content_types
+            .as_deref()
+            .map(Vec::as_slice)
+            .unwrap_or(SUPPORTED_CONTENT_TYPES)
+    }
+
+    pub(crate) fn handle_error(
+        &self,
+        err: PayloadError,
+        req: &HttpRequest,
+    ) -> Result<actix_web::Error, PayloadError> {
+        match &self.err_handler {
+            Some(handler) => Ok(handler(err, req)),
+            None => Err(err),
+        }
+    }
+
+    /// Extract the config from app data. Check both `T` and `Data<T>`, in that order, and fall
+    /// back to the default config.
+    pub(crate) fn from_req(req: &HttpRequest) -> &Self {
+        req.app_data::<Self>()
+            .or_else(|| req.app_data::<web::Data<Self>>().map(|d| d.as_ref()))
+            .unwrap_or(&DEFAULT_CONFIG)
+    }
+}
+
+/// `ContentType` is neither `Clone` nor `Copy`. Content types outside of
+/// `SUPPORTED_CONTENT_TYPES` are never negotiated and are not copied.
+fn copy_content_type(content_type: &ContentType) -> Option<ContentType> {
+    match content_type {
+        ContentType::Json => Some(ContentType::Json),
+        ContentType::Yaml => Some(ContentType::Yaml),
+        ContentType::Toml => Some(ContentType::Toml),
+        ContentType::Json5 => Some(ContentType::Json5),
+        ContentType::Ron => Some(ContentType::Ron),
+        ContentType::Lexpr => Some(ContentType::Lexpr),
+        ContentType::Url => Some(ContentType::Url),
+        ContentType::Cbor => Some(ContentType::Cbor),
+        ContentType::MessagePack => Some(ContentType::MessagePack),
+        ContentType::Bson => Some(ContentType::Bson),
+        ContentType::FlexBuffers => Some(ContentType::FlexBuffers),
+        ContentType::Postcard => Some(ContentType::Postcard),
+        ContentType::Pickle => Some(ContentType::Pickle),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+/// Allow shared refs used as default.
+const DEFAULT_CONFIG: RicksponseConfig = RicksponseConfig {
+    limit: DEFAULT_LIMIT,
+    stream_limit: DEFAULT_STREAM_LIMIT,
+    buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+    decompressed_limit: DEFAULT_LIMIT,
+    compression_ratio: DEFAULT_COMPRESSION_RATIO,
+    content_types: None,
+    err_handler: None,
+    envelope: None,
+};
+
+impl Default for RicksponseConfig {
+    fn default() -> Self {
+        DEFAULT_CONFIG
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::entity::negotiation::SUPPORTED_CONTENT_TYPES;
+    use crate::{negotiated_error, Ricksponse, RicksponseConfig};
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::{error, http::header, web, App, HttpResponse};
+    use http::StatusCode;
+    use simple_serde::ContentType;
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Info {
+        name: String,
+    }
+
+    async fn echo(info: Ricksponse<Info>) -> Ricksponse<String> {
+        match info {
+            Ricksponse::Data { data, .. } => Ricksponse::OK(data.name),
+            Ricksponse::Error { .. } => Ricksponse::BAD_REQUEST("rejected".to_string()),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_config_limit() {
+        let app = init_service(
+            App::new()
+                .app_data(RicksponseConfig::default().limit(8))
+                .route("/", web::post().to(echo)),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .insert_header(header::ContentType::json())
+            .set_payload(r#"{"name":"a rather long name"}"#)
+            .to_request();
+        let res = call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_config_error_handler() {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(RicksponseConfig::default().error_handler(
+                    |err, _req| {
+                        error::InternalError::from_response(err, HttpResponse::Conflict().into())
+                            .into()
+                    },
+                )))
+                .route("/", web::post().to(echo)),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .insert_header(header::ContentType::json())
+            .set_payload("{")
+            .to_request();
+        let res = call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+    }
+
+    #[actix_web::test]
+    async fn test_config_negotiated_error_handler() {
+        let app = init_service(
+            App::new()
+                .app_data(RicksponseConfig::default().error_handler(negotiated_error))
+                .route("/", web::post().to(echo)),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .insert_header(header::ContentType::json())
+            .insert_header((header::ACCEPT, "application/yaml"))
+            .set_payload("{")
+            .to_request();
+        let res = call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+yaml"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_config_reject_on_error() {
+        let app = init_service(
+            App::new()
+                .app_data(RicksponseConfig::default().reject_on_error())
+                .route("/", web::post().to(echo)),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .insert_header(header::ContentType::json())
+            .set_payload("{")
+            .to_request();
+        let res = call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+        let problem: serde_json::Value = serde_json::from_slice(&read_body(res).await).unwrap();
+        assert_eq!(problem["code"], "payload.deserialize");
+    }
+
+    #[actix_web::test]
+    async fn test_config_content_types() {
+        let app = init_service(
+            App::new().service(
+                web::scope("/yaml")
+                    .app_data(
+                        RicksponseConfig::default()
+                            .content_types(vec![ContentType::Json])
+                            .default_content_type(ContentType::Yaml),
+                    )
+                    .route("", web::post().to(echo)),
+            ),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/yaml")
+            .insert_header(header::ContentType::json())
+            .set_payload(r#"{"name":"rick"}"#)
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/yaml"
+        );
+
+        let req = TestRequest::post()
+            .uri("/yaml")
+            .insert_header((header::CONTENT_TYPE, "application/toml"))
+            .set_payload(r#"name = "rick""#)
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_default_content_type_order() {
+        let config = RicksponseConfig::default().default_content_type(ContentType::Yaml);
+        let allowed = config.allowed_content_types();
+        assert_eq!(allowed.len(), SUPPORTED_CONTENT_TYPES.len());
+        assert_eq!(
+            allowed[..3],
+            [ContentType::Yaml, ContentType::Json, ContentType::Toml]
+        );
+
+        let config = RicksponseConfig::default()
+            .content_types(vec![ContentType::Json])
+            .default_content_type(ContentType::Cbor);
+        assert_eq!(
+            config.allowed_content_types(),
+            [ContentType::Cbor, ContentType::Json]
+        );
+    }
+}
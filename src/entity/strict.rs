@@ -0,0 +1,252 @@
+use crate::entity::negotiation::MediaType;
+use crate::entity::payload_control::{DefaultControl, PayloadControl};
+use crate::entity::payload_error::PayloadError;
+use crate::entity::payload_future::{FromPayload, PayloadFuture};
+use crate::entity::validation::{Validate, Validated};
+use actix_web::{FromRequest, HttpRequest};
+use serde::de::DeserializeOwned;
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+/// Extracts a payload the way `Ricksponse` does, but rejects the request when it fails to.
+///
+/// The handler is only called with a decoded payload. A payload that fails to extract is answered
+/// with the status of the `PayloadError`, eg. `400 Bad Request`, `413 Payload Too Large` or
+/// `415 Unsupported Media Type`, and problem details in the format negotiated with the client,
+/// unless a `RicksponseConfig::error_handler` rejects it differently. Limits are taken from the
+/// control `C`, see `PayloadControl`, wrapped in `Validated` the payload is validated as well.
+///
+/// ```rust
+/// use ricksponse::Strict;
+/// use serde_derive::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Order {
+///     item: String,
+/// }
+///
+/// async fn order(order: Strict<Order>) -> String {
+///     format!("ordered {}", order.item)
+/// }
+/// ```
+pub struct Strict<T, C = DefaultControl> {
+    data: T,
+    media_type: Option<MediaType>,
+    _control: PhantomData<C>,
+}
+
+impl<T, C> Strict<T, C> {
+    /// The decoded payload.
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+
+    /// The media type the payload was sent as.
+    pub fn media_type(&self) -> Option<&MediaType> {
+        self.media_type.as_ref()
+    }
+}
+
+impl<T, C> Deref for Strict<T, C> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl<T, C> DerefMut for Strict<T, C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.data
+    }
+}
+
+impl<T: Debug, C> Debug for Strict<T, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Strict")
+            .field("data", &self.data)
+            .field("media_type", &self.media_type)
+            .finish()
+    }
+}
+
+impl<T, C> FromPayload<T> for Strict<T, C> {
+    fn from_payload(
+        res: Result<T, PayloadError>,
+        media_type: Option<MediaType>,
+    ) -> Result<Self, PayloadError> {
+        res.map(|data| Strict {
+            data,
+            media_type,
+            _control: PhantomData,
+        })
+    }
+}
+
+impl<T, C> FromRequest for Strict<T, C>
+where
+    T: DeserializeOwned,
+    C: PayloadControl,
+{
+    type Error = actix_web::Error;
+    type Future = PayloadFuture<C, T, Strict<T, C>>;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, payload: &mut actix_http::Payload) -> Self::Future {
+        PayloadFuture::new(req.clone(), payload)
+    }
+}
+
+impl<T, C> FromRequest for Strict<T, Validated<C>>
+where
+    T: DeserializeOwned + Validate,
+    C: PayloadControl,
+{
+    type Error = actix_web::Error;
+    type Future = PayloadFuture<C, T, Strict<T, Validated<C>>>;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, payload: &mut actix_http::Payload) -> Self::Future {
+        PayloadFuture::validated(req.clone(), payload)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ContentType, PayloadControl, RicksponseConfig, Strict};
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::{error, http::header, web, App, HttpResponse};
+    use http::StatusCode;
+
+    #[derive(Deserialize, Debug)]
+    struct Order {
+        item: String,
+    }
+
+    async fn order(order: Strict<Order>) -> String {
+        format!("ordered {}", order.item)
+    }
+
+    struct JsonOnly;
+
+    impl PayloadControl for JsonOnly {
+        const FORMATS: Option<&'static [ContentType]> = Some(&[ContentType::Json]);
+    }
+
+    #[actix_web::test]
+    async fn test_strict() {
+        let app = init_service(App::new().route("/", web::post().to(order))).await;
+
+        let req = TestRequest::post()
+            .insert_header(header::ContentType::json())
+            .set_payload(r#"{"item":"bullet"}"#)
+            .to_request();
+        let res = call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(read_body(res).await, "ordered bullet");
+    }
+
+    #[actix_web::test]
+    async fn test_strict_rejects() {
+        let app = init_service(
+            App::new()
+                .app_data(RicksponseConfig::default().limit(8))
+                .route("/", web::post().to(order)),
+        )
+        .await;
+
+        let cases = [
+            ("identity", "{", StatusCode::BAD_REQUEST),
+            (
+                "identity",
+                r#"{"item":"a rather long item"}"#,
+                StatusCode::PAYLOAD_TOO_LARGE,
+            ),
+            ("compress", "{}", StatusCode::UNSUPPORTED_MEDIA_TYPE),
+        ];
+        for (encoding, body, status) in cases {
+            let req = TestRequest::post()
+                .insert_header(header::ContentType::json())
+                .insert_header((header::CONTENT_ENCODING, encoding))
+                .insert_header((header::ACCEPT, "application/yaml"))
+                .set_payload(body)
+                .to_request();
+            let res = call_service(&app, req).await;
+
+            assert_eq!(res.status(), status, "{}", body);
+            assert_eq!(
+                res.headers().get(header::CONTENT_TYPE).unwrap(),
+                "application/problem+yaml"
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_strict_advertises_accepted_types() {
+        let app = init_service(
+            App::new()
+                .app_data(
+                    RicksponseConfig::default()
+                        .content_types(vec![ContentType::Json, ContentType::Yaml]),
+                )
+                .route("/", web::post().to(order))
+                .route(
+                    "/",
+                    web::patch().to(|order: Strict<Order, JsonOnly>| async move {
+                        format!("patched {}", order.item)
+                    }),
+                ),
+        )
+        .await;
+
+        let cases = [
+            (
+                TestRequest::post(),
+                "accept-post",
+                "application/json, application/yaml",
+            ),
+            (TestRequest::patch(), "accept-patch", "application/json"),
+        ];
+        for (req, name, accepted) in cases {
+            let req = req
+                .insert_header((header::CONTENT_TYPE, "application/x-pickle"))
+                .set_payload("item")
+                .to_request();
+            let res = call_service(&app, req).await;
+
+            assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+            assert_eq!(res.headers().get(name).unwrap(), accepted);
+        }
+
+        let req = TestRequest::patch()
+            .insert_header((header::CONTENT_TYPE, "application/yaml"))
+            .set_payload("item: bullet")
+            .to_request();
+        let res = call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert!(res.headers().get("accept-post").is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_strict_error_handler() {
+        let app = init_service(
+            App::new()
+                .app_data(RicksponseConfig::default().error_handler(|err, _req| {
+                    error::InternalError::from_response(err, HttpResponse::Conflict().into()).into()
+                }))
+                .route("/", web::post().to(order)),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .insert_header(header::ContentType::json())
+            .set_payload("{")
+            .to_request();
+        let res = call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+    }
+}
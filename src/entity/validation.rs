@@ -0,0 +1,332 @@
+use crate::entity::api_error::ApiError;
+use crate::entity::payload_control::DefaultControl;
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+
+/// Checks a payload after it was decoded.
+///
+/// The extractors validate the payload when the control of the payload is wrapped in
+/// `Validated`, eg. `Ricksponse<Order, Validated>` or `Ricksponse<Order, Validated<Upload>>`.
+/// A payload failing validation never reaches the handler, the request is rejected with
+/// `422 Unprocessable Entity` and problem details listing the failed fields, encoded in the
+/// negotiated format. A `RicksponseConfig::error_handler` is given the chance to reject it
+/// differently.
+///
+/// ```rust
+/// use ricksponse::{Ricksponse, Validate, Validated, ValidationErrors};
+/// use serde_derive::Deserialize;
+///
+/// #[derive(Deserialize, Debug)]
+/// struct Order {
+///     item: String,
+///     quantity: u32,
+/// }
+///
+/// impl Validate for Order {
+///     fn validate(&self) -> Result<(), ValidationErrors> {
+///         let mut errors = ValidationErrors::new();
+///         if self.item.is_empty() {
+///             errors.add("item", "must not be empty");
+///         }
+///         if self.quantity == 0 {
+///             errors.add("quantity", "must be at least 1");
+///         }
+///         errors.into_result()
+///     }
+/// }
+///
+/// async fn order(order: Ricksponse<Order, Validated>) -> String {
+///     format!("{:?}", order)
+/// }
+/// ```
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+impl<T: Validate> Validate for Option<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.as_ref().map_or(Ok(()), Validate::validate)
+    }
+}
+
+impl<T: Validate> Validate for Vec<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        for (i, item) in self.iter().enumerate() {
+            errors.nest(format!("[{}]", i), item.validate());
+        }
+        errors.into_result()
+    }
+}
+
+/// A `PayloadControl` wrapper turning on validation of the payload, see `Validate`. Limits and
+/// formats are taken from the wrapped control.
+pub struct Validated<C = DefaultControl>(PhantomData<C>);
+
+/// A field that failed validation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// The path of the field in the payload, eg. `items[0].quantity`, empty for the payload as a
+    /// whole.
+    pub field: String,
+    pub message: String,
+}
+
+/// The fields of a payload that failed validation.
+///
+/// Rendered as `422 Unprocessable Entity` problem details, the fields are listed in the `details`
+/// extension member.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationErrors {
+    errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an error of the field at `field`.
+    pub fn add<F: Into<String>, M: Into<String>>(&mut self, field: F, message: M) {
+        self.errors.push(FieldError {
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Add the errors of a nested value, their fields are prefixed by the path of the value.
+    pub fn nest<P: Display>(&mut self, path: P, result: Result<(), ValidationErrors>) {
+        if let Err(errors) = result {
+            let path = path.to_string();
+            self.errors
+                .extend(errors.errors.into_iter().map(|mut error| {
+                    error.field = match error.field.as_str() {
+                        "" => path.clone(),
+                        field if field.starts_with('[') || path.is_empty() => {
+                            format!("{}{}", path, field)
+                        }
+                        field => format!("{}.{}", path, field),
+                    };
+                    error
+                }));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, FieldError> {
+        self.errors.iter()
+    }
+
+    /// `Ok` when no error was added.
+    pub fn into_result(self) -> Result<(), Self> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl IntoIterator for ValidationErrors {
+    type Item = FieldError;
+    type IntoIter = std::vec::IntoIter<FieldError>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.into_iter()
+    }
+}
+
+impl Display for ValidationErrors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.errors.as_slice() {
+            [error] if error.field.is_empty() => write!(f, "{}", error.message),
+            [error] => write!(f, "{}: {}", error.field, error.message),
+            errors => write!(f, "{} fields failed validation.", errors.len()),
+        }
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+impl ApiError for ValidationErrors {
+    fn status(&self) -> u16 {
+        422
+    }
+
+    fn code(&self) -> Option<&str> {
+        Some("payload.validation")
+    }
+
+    fn message(&self) -> Option<String> {
+        Some(self.to_string())
+    }
+
+    fn details(&self) -> Option<Value> {
+        serde_json::to_value(&self.errors).ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ApiError, ProblemDetails, ValidationErrors};
+    use crate::{Ricksponse, RicksponseConfig, Validate, Validated};
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::{error, http::header, web, App, HttpResponse};
+    use http::StatusCode;
+    use serde_json::json;
+    use simple_serde::{Decoded, SimpleDecoder};
+
+    #[derive(Deserialize, Debug)]
+    struct Line {
+        item: String,
+        quantity: u32,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Order {
+        customer: String,
+        lines: Vec<Line>,
+    }
+
+    impl Validate for Line {
+        fn validate(&self) -> Result<(), ValidationErrors> {
+            let mut errors = ValidationErrors::new();
+            if self.item.is_empty() {
+                errors.add("item", "must not be empty");
+            }
+            if self.quantity == 0 {
+                errors.add("quantity", "must be at least 1");
+            }
+            errors.into_result()
+        }
+    }
+
+    impl Validate for Order {
+        fn validate(&self) -> Result<(), ValidationErrors> {
+            let mut errors = ValidationErrors::new();
+            if self.customer.is_empty() {
+                errors.add("customer", "must not be empty");
+            }
+            errors.nest("lines", self.lines.validate());
+            errors.into_result()
+        }
+    }
+
+    async fn order(order: Ricksponse<Order, Validated>) -> Ricksponse<String> {
+        match order {
+            Ricksponse::Data { data, .. } => Ricksponse::OK(data.customer),
+            Ricksponse::Error { .. } => Ricksponse::BAD_REQUEST("rejected".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_nested_paths() {
+        let order = Order {
+            customer: String::new(),
+            lines: vec![
+                Line {
+                    item: "bullet".to_string(),
+                    quantity: 1,
+                },
+                Line {
+                    item: String::new(),
+                    quantity: 0,
+                },
+            ],
+        };
+        let errors = order.validate().unwrap_err();
+
+        assert_eq!(errors.to_string(), "3 fields failed validation.");
+        assert_eq!(
+            errors.details().unwrap(),
+            json!([
+                {"field": "customer", "message": "must not be empty"},
+                {"field": "lines[1].item", "message": "must not be empty"},
+                {"field": "lines[1].quantity", "message": "must be at least 1"},
+            ])
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_valid_payload() {
+        let app = init_service(App::new().route("/", web::post().to(order))).await;
+
+        let req = TestRequest::post()
+            .insert_header(header::ContentType::json())
+            .set_payload(r#"{"customer":"rick","lines":[{"item":"bullet","quantity":2}]}"#)
+            .to_request();
+        let res = call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_payload() {
+        let app = init_service(App::new().route("/", web::post().to(order))).await;
+
+        let req = TestRequest::post()
+            .insert_header(header::ContentType::json())
+            .insert_header((header::ACCEPT, "application/yaml"))
+            .set_payload(r#"{"customer":"","lines":[{"item":"bullet","quantity":0}]}"#)
+            .to_request();
+        let res = call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+yaml"
+        );
+        let problem: Decoded<ProblemDetails> =
+            read_body(res).await.to_vec().decode("yaml").unwrap();
+        let problem = problem.into();
+        assert_eq!(problem.extensions["code"], "payload.validation");
+        assert_eq!(
+            problem.extensions["details"],
+            json!([
+                {"field": "customer", "message": "must not be empty"},
+                {"field": "lines[0].quantity", "message": "must be at least 1"},
+            ])
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_payload_error_handler() {
+        let app = init_service(
+            App::new()
+                .app_data(RicksponseConfig::default().error_handler(|err, _req| {
+                    error::InternalError::from_response(err, HttpResponse::Conflict().into()).into()
+                }))
+                .route("/", web::post().to(order)),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .insert_header(header::ContentType::json())
+            .set_payload(r#"{"customer":"","lines":[]}"#)
+            .to_request();
+        let res = call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+    }
+
+    #[actix_web::test]
+    async fn test_undecodable_payload_reaches_handler() {
+        let app = init_service(App::new().route("/", web::post().to(order))).await;
+
+        let req = TestRequest::post()
+            .insert_header(header::ContentType::json())
+            .set_payload("{")
+            .to_request();
+        let res = call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
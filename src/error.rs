@@ -1,10 +1,7 @@
 use crate::entity;
-use actix_http::{
-    body::BoxBody,
-    header::{self, ToStrError},
-    Response, StatusCode,
-};
-use actix_web::{web::BytesMut, HttpResponse, ResponseError};
+use crate::entity::api_error::{error_response, ApiError};
+use actix_http::{body::BoxBody, error::HttpError, header::ToStrError, StatusCode};
+use actix_web::{HttpRequest, HttpResponse, Responder, ResponseError};
 use std::fmt::{Debug, Display, Formatter};
 
 #[derive(Debug)]
@@ -18,11 +15,13 @@ pub enum Error {
     Infallible,
     NoPayloadSizeDefinitionInHeader,
     FailedToParseToInt(std::num::ParseIntError),
+    InvalidMediaType(String),
+    NoAcceptableContentType,
+    InvalidHeader(HttpError),
+    PreconditionFailed,
+    StreamFailed(Box<dyn ApiError>),
 }
 
-unsafe impl Send for Error {}
-unsafe impl Sync for Error {}
-
 impl From<entity::payload_error::PayloadError> for Error {
     fn from(e: entity::payload_error::PayloadError) -> Self {
         Self::PayloadError(e)
@@ -62,64 +61,187 @@ impl Display for Error {
             Error::SerializationDeserializationError(e) => {
                 write!(f, "Serialization/Deserialization error: {:?}", e)
             }
-            Error::PayloadError(e) => write!(f, "Payload Error: {:?}", e),
-            Error::FailedToGetContentTypeFromHeader => write!(f, "FailedToGetContentTypeFromHeader"),
+            Error::PayloadError(e) => write!(f, "Payload Error: {}", e),
+            Error::FailedToGetContentTypeFromHeader => {
+                write!(f, "No supported content type in the Content-Type header")
+            }
             Error::Infallible => write!(f, "Infallible - you should never end up here!! - Please open a bug with a stacktrace using RUST_BACKTRACE=full"),
-            Error::NoPayloadSizeDefinitionInHeader => write!(f, "NoPayloadSizeDefinitionInHeader"),
-            Error::FailedToParseToInt(_) => write!(f, "FailedToParseToInt"),
+            Error::NoPayloadSizeDefinitionInHeader => write!(f, "No Content-Length header"),
+            Error::FailedToParseToInt(e) => write!(f, "Failed to parse integer: {}", e),
+            Error::InvalidMediaType(s) => write!(f, "Invalid media type: {}", s),
+            Error::NoAcceptableContentType => {
+                write!(f, "None of the accepted content types can be produced")
+            }
+            Error::InvalidHeader(e) => write!(f, "Invalid response header: {}", e),
+            Error::PreconditionFailed => {
+                write!(f, "The resource does not match the preconditions of the request")
+            }
+            Error::StreamFailed(e) => write!(f, "Failed to produce a streamed item: {:?}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::FailedToMapHeaderToStr(e) => Some(e),
+            Error::PayloadError(e) => Some(e),
+            Error::FailedToParseToInt(e) => Some(e),
+            Error::InvalidHeader(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl ApiError for Error {
+    fn status(&self) -> u16 {
+        self.status_code().as_u16()
+    }
+
+    fn code(&self) -> Option<&str> {
+        match self {
+            Error::FailedToMatchAnyContentType => Some("content_type.unsupported"),
+            Error::PayloadTooLarge(_) => Some("payload.too_large"),
+            Error::FailedToMapHeaderToStr(_) => Some("header.invalid"),
+            Error::SerializationDeserializationError(_) => Some("serialization"),
+            Error::PayloadError(e) => e.code(),
+            Error::FailedToGetContentTypeFromHeader => Some("content_type.missing"),
+            Error::Infallible => None,
+            Error::NoPayloadSizeDefinitionInHeader => Some("content_length.missing"),
+            Error::FailedToParseToInt(_) => Some("content_length.invalid"),
+            Error::InvalidMediaType(_) => Some("media_type.invalid"),
+            Error::NoAcceptableContentType => Some("content_type.not_acceptable"),
+            Error::InvalidHeader(_) => Some("response.invalid_header"),
+            Error::PreconditionFailed => Some("precondition.failed"),
+            Error::StreamFailed(e) => e.code(),
+        }
+    }
+
+    /// Client errors explain themselves, server errors are not detailed to the client.
+    fn message(&self) -> Option<String> {
+        if self.status_code().is_client_error() {
+            Some(self.to_string())
+        } else {
+            None
         }
     }
 }
 
 impl ResponseError for Error {
     fn status_code(&self) -> StatusCode {
-        StatusCode::INTERNAL_SERVER_ERROR
+        match self {
+            Error::FailedToMatchAnyContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Error::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Error::FailedToMapHeaderToStr(_) => StatusCode::BAD_REQUEST,
+            Error::SerializationDeserializationError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::PayloadError(e) => e.status_code(),
+            Error::FailedToGetContentTypeFromHeader => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Error::Infallible => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::NoPayloadSizeDefinitionInHeader => StatusCode::LENGTH_REQUIRED,
+            Error::FailedToParseToInt(_) => StatusCode::BAD_REQUEST,
+            Error::InvalidMediaType(_) => StatusCode::BAD_REQUEST,
+            Error::NoAcceptableContentType => StatusCode::NOT_ACCEPTABLE,
+            Error::InvalidHeader(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            Error::StreamFailed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
     }
 
     /// Create response for error
     ///
-    /// Internal server error is generated by default.
-    fn error_response(&self) -> actix_web::HttpResponse {
-        let mut resp = Response::new(self.status_code());
-        let buf = BytesMut::new();
-        resp.headers_mut().insert(
-            header::CONTENT_TYPE,
-            header::HeaderValue::from_static("text/plain; charset=utf-8"),
-        );
-        HttpResponse::from(resp.set_body(BoxBody::new(buf)))
+    /// Without the request at hand the problem details are encoded as `application/problem+json`,
+    /// responding with the error itself negotiates the format from the `Accept` header.
+    fn error_response(&self) -> HttpResponse {
+        self.problem().json_response()
+    }
+}
+
+impl Responder for Error {
+    type Body = BoxBody;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+        error_response(&self, req)
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::Error;
+    use crate::{Error, ProblemDetails};
+    use actix_http::{header, StatusCode};
+    use actix_web::test::TestRequest;
+    use actix_web::{body, Responder, ResponseError};
+    use std::error::Error as _;
 
-    use actix_http::{
-        body::BoxBody,
-        header::{self},
-        Response, StatusCode,
-    };
-    use actix_web::{web::BytesMut, HttpResponse, ResponseError};
+    #[test]
+    fn error_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Error>();
+    }
 
     #[test]
     fn default_error_code() {
         let err = Error::Infallible;
         assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
     }
+
     #[test]
-    fn default_error_response() {
-        let err = Error::Infallible;
+    fn error_status_codes() {
+        assert_eq!(
+            Error::FailedToMatchAnyContentType.status_code(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+        assert_eq!(
+            Error::PayloadTooLarge("1 GB".to_string()).status_code(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        assert_eq!(
+            Error::from("x".parse::<usize>().unwrap_err()).status_code(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            Error::NoAcceptableContentType.status_code(),
+            StatusCode::NOT_ACCEPTABLE
+        );
+    }
 
-        let mut resp = Response::new(StatusCode::INTERNAL_SERVER_ERROR);
-        let mut buf = BytesMut::new();
-        resp.headers_mut().insert(
-            header::CONTENT_TYPE,
-            header::HeaderValue::from_static("text/plain; charset=utf-8"),
+    #[test]
+    fn error_source() {
+        let err = Error::from("x".parse::<usize>().unwrap_err());
+        assert_eq!(
+            err.source().unwrap().to_string(),
+            "invalid digit found in string"
+        );
+        assert!(Error::Infallible.source().is_none());
+    }
+
+    #[actix_web::test]
+    async fn default_error_response() {
+        let res = Error::Infallible.error_response();
+
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+        let body = body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(
+            serde_json::from_slice::<ProblemDetails>(&body).unwrap(),
+            ProblemDetails::new(500)
         );
+    }
+
+    #[actix_web::test]
+    async fn negotiated_error_response() {
+        let req = TestRequest::default()
+            .insert_header((header::ACCEPT, "application/yaml"))
+            .to_http_request();
+
+        let res = Error::PayloadTooLarge("1 GB".to_string()).respond_to(&req);
 
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
         assert_eq!(
-            format!("{:?}", err.error_response()),
-            format!("{:?}", HttpResponse::from(resp.set_body(BoxBody::new(buf))))
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/problem+yaml"
         );
     }
 }
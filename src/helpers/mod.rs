@@ -33,6 +33,6 @@ mod test {
         hash_set.insert("foo");
         hash_set.insert("bar");
 
-        assert!(!get_from_hash_set(hash_set).is_ok())
+        assert!(get_from_hash_set(hash_set).is_err())
     }
 }
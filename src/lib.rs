@@ -6,12 +6,13 @@ extern crate futures_core;
 extern crate hateoas as hateoas_response;
 extern crate railsgun;
 extern crate serde;
+extern crate serde_cbor;
 #[macro_use]
 extern crate serde_derive;
-extern crate simple_serde;
-
-#[cfg(test)]
 extern crate serde_json;
+extern crate simple_serde;
+// Lets the derive macros refer to `::ricksponse` from within this crate as well.
+extern crate self as ricksponse;
 
 mod entity;
 mod error;
@@ -19,15 +20,46 @@ mod helpers;
 
 pub type Result<T> = std::result::Result<T, error::Error>;
 
-const MAX_SIZE: usize = 41_943_040;
-
-pub use entity::{payload_control::*, payload_error::*, ricksponse::ricksponse::*};
+pub use entity::{
+    api_error::*,
+    conditional::{evaluate_preconditions, Precondition},
+    decode::{DecodeError, ErrorLocation},
+    envelope::Envelope,
+    payload_control::*,
+    payload_error::*,
+    problem_details::*,
+    ricksponse::parts::*,
+    ricksponse::ricksponse::*,
+    ricksponse::sse::{Event, LastEventId, RicksponseSse},
+    ricksponse::stream::RicksponseStream,
+    ricksponse_config::*,
+    strict::Strict,
+    validation::{FieldError, Validate, Validated, ValidationErrors},
+};
 
 pub use error::Error;
+#[cfg(feature = "derive")]
+pub use ricksponse_derive::{ApiError, PayloadControl};
+pub use simple_serde::ContentType;
+#[doc(hidden)]
+pub mod __private {
+    //! Used by the code the derive macros generate, not public API.
+    pub use serde_json::Value;
+
+    pub fn to_details<T: serde::Serialize>(details: &T) -> Option<Value> {
+        serde_json::to_value(details).ok()
+    }
+}
+
 pub mod helpers_prelude {
     pub use crate::helpers::get_from_hash_set;
 }
 
+pub mod negotiation {
+    pub use crate::entity::negotiation::*;
+}
+
+#[cfg(feature = "hateoas")]
 pub mod hateoas {
     pub use crate::entity::hateoas::*;
     pub mod prelude {
@@ -40,6 +72,7 @@ mod test {
     use crate::Ricksponse;
     use actix_web::{http::header, test, web, App};
 
+    #[allow(dead_code)]
     #[derive(Serialize, Deserialize)]
     pub struct RubberBullet {
         pub name: String,
@@ -57,11 +90,6 @@ mod test {
         }
     }
 
-    const RICKSPONSE_1: &str = r##"
-
-
-    "##;
-
     #[actix_web::test]
     async fn test_ricksponse_string() {
         let app = test::init_service(
@@ -80,9 +108,7 @@ mod test {
         let res = test::call_service(&app, req).await;
         let result = test::read_body(res).await;
 
-        let string =
-            serde_json::from_str::<String>(std::str::from_utf8(&*result).unwrap()).unwrap();
-        println!("{}", string);
+        let string = serde_json::from_str::<String>(std::str::from_utf8(&result).unwrap()).unwrap();
         assert_eq!(string, "welcome!");
     }
 }
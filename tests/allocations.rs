@@ -0,0 +1,110 @@
+//! Measures the allocations of extracting a payload against decoding a copy of the body. The
+//! counting allocator replaces the allocator of the whole test binary, it is kept out of the unit
+//! tests for that reason.
+
+use actix_web::dev::Payload;
+use actix_web::test::TestRequest;
+use actix_web::FromRequest;
+use bytes::BytesMut;
+use futures::StreamExt;
+use ricksponse::{Ricksponse, RicksponseConfig};
+use serde_derive::Deserialize;
+use simple_serde::{ContentType, Decoded, SimpleDecoder};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::future::Future;
+
+/// Counts the allocations of at least `LARGE` bytes made on the current thread, the test runtime
+/// polls on one thread.
+struct Counting;
+
+thread_local! {
+    static LARGE: Cell<usize> = const { Cell::new(usize::MAX) };
+    static COUNT: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count(size: usize) {
+    let _ = LARGE.try_with(|large| {
+        if size >= large.get() {
+            let _ = COUNT.try_with(|c| c.set(c.get() + 1));
+        }
+    });
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+#[derive(Deserialize, Debug)]
+struct Note {
+    text: String,
+}
+
+/// Runs `f` to completion and counts its allocations of at least `large` bytes.
+async fn large_allocations<F: Future>(large: usize, f: F) -> (F::Output, usize) {
+    COUNT.with(|c| c.set(0));
+    LARGE.with(|l| l.set(large));
+    let output = f.await;
+    LARGE.with(|l| l.set(usize::MAX));
+    (output, COUNT.with(Cell::get))
+}
+
+/// Extracts the way the body was decoded before, from a copy of the buffer it was read into.
+async fn extract_copied(mut payload: Payload, capacity: usize) -> Note {
+    let mut buf = BytesMut::with_capacity(capacity);
+    while let Some(chunk) = payload.next().await {
+        buf.extend_from_slice(&chunk.unwrap());
+    }
+    let note: Decoded<Note> = buf.to_vec().as_slice().decode(&ContentType::Json).unwrap();
+    note.into()
+}
+
+#[actix_web::test]
+async fn test_decode_does_not_copy_the_body() {
+    let text = "a".repeat(1 << 20);
+    let body = format!(r#"{{"text":"{}"}}"#, text);
+    let request = || {
+        TestRequest::post()
+            .insert_header(("content-type", "application/json"))
+            .app_data(RicksponseConfig::default().buffer_capacity(body.len()))
+            .set_payload(body.clone())
+            .to_http_parts()
+    };
+
+    let (_, payload) = request();
+    let (copied, copying) =
+        large_allocations(text.len(), extract_copied(payload, body.len())).await;
+    assert_eq!(copied.text, text);
+
+    let (req, mut payload) = request();
+    let (note, extracting) = large_allocations(
+        text.len(),
+        Ricksponse::<Note>::from_request(&req, &mut payload),
+    )
+    .await;
+    match note {
+        Ok(Ricksponse::Data { data, .. }) => assert_eq!(data.text, text),
+        note => panic!("{:?}", note),
+    }
+    assert!(
+        extracting < copying,
+        "{} allocations of the body size extracting, {} copying",
+        extracting,
+        copying
+    );
+}